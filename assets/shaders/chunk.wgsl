@@ -0,0 +1,54 @@
+#import bevy_pbr::{
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::alpha_discard,
+}
+
+#ifdef PREPASS_PIPELINE
+#import bevy_pbr::{
+    prepass_io::{VertexOutput, FragmentOutput},
+    pbr_deferred_functions::deferred_output,
+}
+#else
+#import bevy_pbr::{
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_functions::{apply_pbr_lighting, main_pass_post_lighting_processing},
+}
+#endif
+
+struct TiledAtlas {
+    tile_size: vec2<f32>,
+    sky_light: f32,
+}
+
+@group(2) @binding(100)
+var<uniform> tiled_atlas: TiledAtlas;
+
+@fragment
+fn fragment(
+    in: VertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+    // uv counts blocks along the quad, uv_b is where the block texture starts in the atlas,
+    // so the texture repeats once per block
+    var tiled = in;
+    tiled.uv = in.uv_b + fract(in.uv) * tiled_atlas.tile_size;
+#ifdef VERTEX_COLORS
+    // red is the brightness of sky light and green of block light,
+    // only sky light changes with the time of day
+    let light = max(in.color.r * tiled_atlas.sky_light, in.color.g);
+    tiled.color = vec4(vec3(light), in.color.a);
+#endif
+
+    var pbr_input = pbr_input_from_standard_material(tiled, is_front);
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+#ifdef PREPASS_PIPELINE
+    let out = deferred_output(tiled, pbr_input);
+#else
+    var out: FragmentOutput;
+    out.color = apply_pbr_lighting(pbr_input);
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+#endif
+
+    return out;
+}
@@ -1,12 +1,12 @@
 use bevy::{
     asset::{AssetLoader, AsyncReadExt},
-    prelude::*,
     utils::BoxedFuture,
 };
 use serde_json::from_slice;
 use thiserror::Error;
 
-use super::{Block, BlockJson};
+use super::{Block, BlockDrop, BlockJson, StateTextures};
+use crate::world::light::MAX_LIGHT;
 
 #[derive(Default)]
 pub struct BlockAssetLoader;
@@ -41,12 +41,36 @@ impl AssetLoader for BlockAssetLoader {
             // and all textures must be loaded, so this must return handles
             // to existing assets instead of reloading them
             let block_textures = block_json.textures.map(|v| load_context.load(v).id());
+            let state_textures = block_json
+                .state_textures
+                .into_iter()
+                .map(|state_textures| StateTextures {
+                    when: state_textures.when,
+                    textures: state_textures.textures.map(|v| load_context.load(v).id()),
+                })
+                .collect();
+
+            let drops = match block_json.drops {
+                Some(drops) => drops.into_iter().map(BlockDrop::from).collect(),
+                None => vec![BlockDrop {
+                    item: block_json.id.clone().into(),
+                    count: 1..=1,
+                    probability: 1.0,
+                }],
+            };
 
             Ok(Block {
                 id: block_json.id.into(),
                 name: block_json.name,
                 textures: block_textures,
                 opacity: block_json.opacity.into(),
+                hardness: block_json.hardness,
+                drops,
+                properties: block_json.properties.into(),
+                state_textures,
+                model: block_json.model,
+                collision: block_json.collision,
+                light_emission: block_json.light_emission.min(MAX_LIGHT),
             })
         })
     }
@@ -1,14 +1,25 @@
+use std::{collections::BTreeMap, ops::RangeInclusive};
+
 use bevy::{asset::LoadedFolder, prelude::*};
 use serde::Deserialize;
 
 use crate::{
+    chunk::mesh::Face,
     common::{AppState, SetupState},
     registry::BlockRegistry,
+    world::light::StateLight,
 };
 
-use self::{asset::BlockAssetLoader, systems::*};
+use self::{
+    asset::BlockAssetLoader,
+    model::{BlockModel, BlockShape, CollisionBox},
+    state::BlockProperties,
+    systems::*,
+};
 
 pub mod asset;
+pub mod model;
+pub mod state;
 mod systems;
 
 pub const BLOCK_HALF_SIZE: f32 = 0.5;
@@ -53,17 +64,51 @@ pub struct Block {
     pub name: String,
     pub textures: BlockTextures<AssetId<Image>>,
     pub opacity: Opacity,
+    pub hardness: f32,
+    // what the block turns into when broken
+    pub drops: Vec<BlockDrop>,
+    pub properties: BlockProperties,
+    // textures replacing the default ones in some of the states
+    pub state_textures: Vec<StateTextures<AssetId<Image>>>,
+    pub model: BlockModel,
+    // boxes of the model are used, if the block doesn't declare its own
+    pub collision: Option<Vec<CollisionBox>>,
+    // light level the block gives off, from 0 to 15
+    pub light_emission: u8,
+}
+
+// Textures of the block in the states where properties have the listed values
+#[derive(Clone, Debug, Deserialize)]
+pub struct StateTextures<T> {
+    pub when: BTreeMap<String, String>,
+    pub textures: BlockTextures<T>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockDrop {
+    pub item: BlockId,
+    pub count: RangeInclusive<u32>,
+    // chance from 0 to 1 that the drop happens at all
+    pub probability: f32,
 }
 
 #[derive(Clone, Debug)]
 pub struct BlockMesh {
-    pub opacity: Opacity,
     pub textures: BlockTextures<Rect>,
+    pub shape: BlockShape,
+    // sides of the block that hide faces of the neighbours, in Face::ALL order
+    pub occluding_sides: [bool; 6],
+    // full cubes are merged by the greedy mesher, other shapes are meshed one by one
+    pub is_cube: bool,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct BlockId(pub String);
 
+// Runtime id of the block assigned by BlockRegistry
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NumericBlockId(pub u16);
+
 // Textures of a block
 #[derive(Clone, Debug, Deserialize)]
 #[serde(untagged)]
@@ -111,6 +156,56 @@ pub struct BlockJson {
     pub name: String,
     pub textures: BlockTexturesPaths,
     pub opacity: u8,
+    // how long the block takes to mine, 0 breaks instantly
+    #[serde(default)]
+    pub hardness: f32,
+    // block drops itself if there are no drops listed,
+    // empty list means nothing drops
+    pub drops: Option<Vec<BlockDropJson>>,
+    // names of the properties with all of their values, the first one is the default
+    #[serde(default)]
+    pub properties: BTreeMap<String, Vec<String>>,
+    // the first entry whose values match the state is used,
+    // states without a match use the default textures
+    #[serde(default)]
+    pub state_textures: Vec<StateTextures<String>>,
+    #[serde(default)]
+    pub model: BlockModel,
+    // what entities collide with, empty list lets them go through the block
+    pub collision: Option<Vec<CollisionBox>>,
+    #[serde(default)]
+    pub light_emission: u8,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BlockDropJson {
+    pub item: String,
+    // smallest and largest amount that can drop
+    #[serde(default = "BlockDropJson::default_count")]
+    pub count: [u32; 2],
+    #[serde(default = "BlockDropJson::default_probability")]
+    pub probability: f32,
+}
+
+impl BlockDropJson {
+    fn default_count() -> [u32; 2] {
+        [1, 1]
+    }
+
+    fn default_probability() -> f32 {
+        1.0
+    }
+}
+
+impl From<BlockDropJson> for BlockDrop {
+    fn from(value: BlockDropJson) -> Self {
+        let [min, max] = value.count;
+        BlockDrop {
+            item: value.item.into(),
+            count: min..=max.max(min),
+            probability: value.probability,
+        }
+    }
 }
 
 // this holds handle to the loaded textures
@@ -135,6 +230,67 @@ impl From<&str> for BlockId {
     }
 }
 
+impl Block {
+    // Textures of the block in the state
+    pub fn textures_of(&self, state: usize) -> &BlockTextures<AssetId<Image>> {
+        self.state_textures
+            .iter()
+            .find(|state_textures| {
+                state_textures
+                    .when
+                    .iter()
+                    .all(|(name, value)| self.properties.value(state, name) == Some(value.as_str()))
+            })
+            .map_or(&self.textures, |state_textures| &state_textures.textures)
+    }
+
+    // Corners of the boxes entities collide with in the state, from 0 to 1
+    pub fn collision_boxes(&self, state: usize) -> Vec<(Vec3, Vec3)> {
+        if let Some(collision) = &self.collision {
+            return collision
+                .iter()
+                .map(|collision_box| {
+                    (
+                        Vec3::from_array(collision_box.from),
+                        Vec3::from_array(collision_box.to),
+                    )
+                })
+                .collect();
+        }
+        match self.model.shape(&self.properties, state) {
+            BlockShape::Boxes(boxes) => boxes
+                .into_iter()
+                .map(|shape_box| (shape_box.min, shape_box.max))
+                .collect(),
+            BlockShape::Cross => Vec::new(),
+        }
+    }
+
+    // How the block interacts with light in the state,
+    // light only goes through blocks that don't fill the whole cube or can be seen through
+    pub fn state_light(&self, state: usize) -> StateLight {
+        let shape = self.model.shape(&self.properties, state);
+        StateLight {
+            emission: self.light_emission,
+            opaque: self.opacity == Opacity::Opaque
+                && Face::ALL.iter().all(|side| shape.covers(*side)),
+        }
+    }
+}
+
+impl BlockMesh {
+    pub fn new(opacity: Opacity, textures: BlockTextures<Rect>, shape: BlockShape) -> BlockMesh {
+        let occluding_sides =
+            Face::ALL.map(|side| opacity == Opacity::Opaque && shape.covers(side));
+        BlockMesh {
+            textures,
+            is_cube: shape.is_cube(),
+            shape,
+            occluding_sides,
+        }
+    }
+}
+
 impl From<u8> for Opacity {
     fn from(value: u8) -> Self {
         match value {
@@ -300,3 +456,23 @@ impl<T> BlockTextures<T> {
         }
     }
 }
+
+#[cfg(test)]
+impl Block {
+    // Untextured cube, enough for the registry to give it ids and light
+    pub fn test_cube(id: &str, opacity: Opacity, light_emission: u8) -> Block {
+        Block {
+            id: id.into(),
+            name: id.to_string(),
+            textures: BlockTextures::Single(AssetId::default()),
+            opacity,
+            hardness: 0.0,
+            drops: Vec::new(),
+            properties: BlockProperties::default(),
+            state_textures: Vec::new(),
+            model: BlockModel::Cube,
+            collision: None,
+            light_emission,
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use super::state::BlockProperties;
+use crate::chunk::mesh::Face;
+
+// Geometry of the block, declared in the .block file as {"type": "slab"} and so on
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BlockModel {
+    #[default]
+    Cube,
+    // bottom or top half of the cube, depending on the "half" property
+    Slab,
+    // slab with a step on it, the step is on the opposite side of the "facing" property,
+    // so the stairs go up away from the player who placed them
+    Stair,
+    // two crossing planes, for plants
+    Cross,
+    // any number of boxes, coordinates are in blocks from 0 to 1
+    Boxes {
+        boxes: Vec<ModelBox>,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ModelBox {
+    pub from: [f32; 3],
+    pub to: [f32; 3],
+    // part of the texture drawn on the face, [min u, min v, max u, max v] from 0 to 1,
+    // faces without it show the part of the texture the face covers on the full block
+    #[serde(default)]
+    pub uv: FaceUvs,
+}
+
+// Box entities collide with, coordinates are in blocks from 0 to 1
+#[derive(Clone, Debug, Deserialize)]
+pub struct CollisionBox {
+    pub from: [f32; 3],
+    pub to: [f32; 3],
+}
+
+// Same face names as in the block textures
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct FaceUvs {
+    pub top: Option<[f32; 4]>,
+    pub bottom: Option<[f32; 4]>,
+    pub front: Option<[f32; 4]>,
+    pub back: Option<[f32; 4]>,
+    pub left: Option<[f32; 4]>,
+    pub right: Option<[f32; 4]>,
+}
+
+// Model of the block in one of its states, ready to be meshed
+#[derive(Clone, Debug, PartialEq)]
+pub enum BlockShape {
+    Boxes(Vec<ShapeBox>),
+    Cross,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShapeBox {
+    // corners of the box inside of the block, from 0 to 1
+    pub min: Vec3,
+    pub max: Vec3,
+    // uv of every face in Face::ALL order, min is the top left corner of the face
+    pub uvs: [Rect; 6],
+}
+
+impl BlockModel {
+    pub fn shape(&self, properties: &BlockProperties, state: usize) -> BlockShape {
+        let top_half = properties.value(state, "half") == Some("top");
+        let (lower, upper) = if top_half { (0.5, 0.0) } else { (0.0, 0.5) };
+        match self {
+            BlockModel::Cube => BlockShape::cube(),
+            BlockModel::Slab => BlockShape::Boxes(vec![ShapeBox::new(
+                Vec3::new(0.0, lower, 0.0),
+                Vec3::new(1.0, lower + 0.5, 1.0),
+            )]),
+            BlockModel::Stair => {
+                let (step_min, step_max) = match properties.value(state, "facing") {
+                    Some("south") => (Vec3::new(0.0, upper, 0.0), Vec3::new(1.0, upper + 0.5, 0.5)),
+                    Some("east") => (Vec3::new(0.0, upper, 0.0), Vec3::new(0.5, upper + 0.5, 1.0)),
+                    Some("west") => (Vec3::new(0.5, upper, 0.0), Vec3::new(1.0, upper + 0.5, 1.0)),
+                    _ => (Vec3::new(0.0, upper, 0.5), Vec3::new(1.0, upper + 0.5, 1.0)),
+                };
+                BlockShape::Boxes(vec![
+                    ShapeBox::new(Vec3::new(0.0, lower, 0.0), Vec3::new(1.0, lower + 0.5, 1.0)),
+                    ShapeBox::new(step_min, step_max),
+                ])
+            }
+            BlockModel::Cross => BlockShape::Cross,
+            BlockModel::Boxes { boxes } => BlockShape::Boxes(
+                boxes
+                    .iter()
+                    .map(|model_box| {
+                        let mut shape_box = ShapeBox::new(
+                            Vec3::from_array(model_box.from),
+                            Vec3::from_array(model_box.to),
+                        );
+                        for face in Face::ALL {
+                            if let Some([min_u, min_v, max_u, max_v]) = model_box.uv.get(face) {
+                                shape_box.uvs[face as usize] = Rect {
+                                    min: Vec2::new(min_u, min_v),
+                                    max: Vec2::new(max_u, max_v),
+                                };
+                            }
+                        }
+                        shape_box
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl FaceUvs {
+    // Face::Front looks at +z, which is the back of the block textures, see Face::texture
+    fn get(&self, face: Face) -> Option<[f32; 4]> {
+        match face {
+            Face::Front => self.back,
+            Face::Back => self.front,
+            Face::Right => self.right,
+            Face::Left => self.left,
+            Face::Top => self.top,
+            Face::Bottom => self.bottom,
+        }
+    }
+}
+
+impl BlockShape {
+    pub fn cube() -> BlockShape {
+        BlockShape::Boxes(vec![ShapeBox::new(Vec3::ZERO, Vec3::ONE)])
+    }
+
+    // Whether the side of the block is completely covered by the faces of the boxes,
+    // such side hides the face of the neighbour touching it, if the block is opaque
+    pub fn covers(&self, side: Face) -> bool {
+        let BlockShape::Boxes(boxes) = self else {
+            return false;
+        };
+        let (n, a, b) = side.axes();
+        let border = if side.normal()[n] > 0 { 1.0 } else { 0.0 };
+        // rectangles the boxes cover on the side
+        let rects: Vec<Rect> = boxes
+            .iter()
+            .filter(|shape_box| {
+                let face = if border == 1.0 {
+                    shape_box.max[n]
+                } else {
+                    shape_box.min[n]
+                };
+                face == border
+            })
+            .map(|shape_box| Rect {
+                min: Vec2::new(shape_box.min[a], shape_box.min[b]),
+                max: Vec2::new(shape_box.max[a], shape_box.max[b]),
+            })
+            .collect();
+
+        // edges of the rectangles split the side into cells,
+        // every cell is either fully covered by some rectangle or not covered at all
+        let splits = |axis: fn(Vec2) -> f32| {
+            let mut splits: Vec<f32> = rects
+                .iter()
+                .flat_map(|rect| [axis(rect.min), axis(rect.max)])
+                .chain([0.0, 1.0])
+                .filter(|v| (0.0..=1.0).contains(v))
+                .collect();
+            splits.sort_by(f32::total_cmp);
+            splits.dedup();
+            splits
+        };
+        let (us, vs) = (splits(|v| v.x), splits(|v| v.y));
+        us.windows(2).all(|u| {
+            vs.windows(2).all(|v| {
+                let center = Vec2::new(u[0] + u[1], v[0] + v[1]) / 2.0;
+                rects.iter().any(|rect| rect.contains(center))
+            })
+        })
+    }
+
+    pub fn is_cube(&self) -> bool {
+        *self == BlockShape::cube()
+    }
+}
+
+impl ShapeBox {
+    // Box with the textures mapped as if it was cut out of the full block
+    pub fn new(min: Vec3, max: Vec3) -> ShapeBox {
+        let uvs = Face::ALL.map(|face| {
+            let (min_u, max_u, min_v, max_v) = match face {
+                Face::Front | Face::Back => (min.x, max.x, 1.0 - max.y, 1.0 - min.y),
+                Face::Right => (min.z, max.z, 1.0 - max.y, 1.0 - min.y),
+                Face::Left => (1.0 - max.z, 1.0 - min.z, 1.0 - max.y, 1.0 - min.y),
+                Face::Top => (min.x, max.x, 1.0 - max.z, 1.0 - min.z),
+                Face::Bottom => (min.x, max.x, min.z, max.z),
+            };
+            Rect {
+                min: Vec2::new(min_u, min_v),
+                max: Vec2::new(max_u, max_v),
+            }
+        });
+        ShapeBox { min, max, uvs }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::state::BlockProperty;
+
+    fn covered_sides(shape: &BlockShape) -> Vec<Face> {
+        Face::ALL
+            .into_iter()
+            .filter(|side| shape.covers(*side))
+            .collect()
+    }
+
+    #[test]
+    fn cube_covers_every_side() {
+        assert_eq!(covered_sides(&BlockShape::cube()), Face::ALL.to_vec());
+        assert!(covered_sides(&BlockShape::Cross).is_empty());
+    }
+
+    #[test]
+    fn slab_covers_only_its_half() {
+        let properties = BlockProperties(vec![BlockProperty {
+            name: String::from("half"),
+            values: ["bottom", "top"].map(String::from).to_vec(),
+        }]);
+        let bottom = BlockModel::Slab.shape(&properties, 0);
+        assert_eq!(covered_sides(&bottom), vec![Face::Bottom]);
+        let top = BlockModel::Slab.shape(&properties, 1);
+        assert_eq!(covered_sides(&top), vec![Face::Top]);
+    }
+
+    #[test]
+    fn stair_back_is_covered_by_two_boxes() {
+        let properties = BlockProperties::test_stairs();
+        let north = properties.state([("facing", "north")]).unwrap();
+        let shape = BlockModel::Stair.shape(&properties, north);
+        assert_eq!(covered_sides(&shape), vec![Face::Front, Face::Bottom]);
+
+        let east_top = properties
+            .state([("facing", "east"), ("half", "top")])
+            .unwrap();
+        let shape = BlockModel::Stair.shape(&properties, east_top);
+        assert_eq!(covered_sides(&shape), vec![Face::Left, Face::Top]);
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+// Runtime id of the block together with the values of its properties,
+// assigned by BlockRegistry. Just like NumericBlockId it's only valid until the game is closed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BlockStateId(pub u16);
+
+// Property of the block and every value it can have, the first value is the default one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockProperty {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+// Every combination of the property values is a separate state of the block,
+// states are numbered from 0, where every property has its default value
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockProperties(pub Vec<BlockProperty>);
+
+// How the player placed the block
+pub struct Placement {
+    pub look_direction: Vec3,
+    // normal of the face the block was placed against
+    pub normal: Vec3,
+    // point on that face the player clicked
+    pub point: Vec3,
+}
+
+impl From<BTreeMap<String, Vec<String>>> for BlockProperties {
+    fn from(value: BTreeMap<String, Vec<String>>) -> Self {
+        BlockProperties(
+            value
+                .into_iter()
+                .filter(|(_, values)| !values.is_empty())
+                .map(|(name, values)| BlockProperty { name, values })
+                .collect(),
+        )
+    }
+}
+
+impl BlockProperties {
+    pub fn state_count(&self) -> usize {
+        self.0
+            .iter()
+            .map(|property| property.values.len())
+            .product()
+    }
+
+    // Index of the value of every property in the state, first property changes the fastest
+    fn value_indices(&self, state: usize) -> impl Iterator<Item = usize> + '_ {
+        let mut rest = state;
+        self.0.iter().map(move |property| {
+            let value = rest % property.values.len();
+            rest /= property.values.len();
+            value
+        })
+    }
+
+    fn state_of(&self, value_indices: impl Iterator<Item = usize>) -> usize {
+        let mut stride = 1;
+        let mut state = 0;
+        for (property, value) in self.0.iter().zip(value_indices) {
+            state += value * stride;
+            stride *= property.values.len();
+        }
+        state
+    }
+
+    pub fn value(&self, state: usize, name: &str) -> Option<&str> {
+        self.values(state)
+            .find(|(property, _)| *property == name)
+            .map(|(_, value)| value)
+    }
+
+    // Name and value of every property in the state
+    pub fn values(&self, state: usize) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .zip(self.value_indices(state))
+            .map(|(property, value)| (property.name.as_str(), property.values[value].as_str()))
+    }
+
+    // State with the values, properties that are not listed keep their default values.
+    // None if there is no such property or value
+    pub fn state<'a>(&self, values: impl IntoIterator<Item = (&'a str, &'a str)>) -> Option<usize> {
+        let mut indices = vec![0; self.0.len()];
+        for (name, value) in values {
+            let property = self.0.iter().position(|property| property.name == name)?;
+            indices[property] = self.0[property].values.iter().position(|v| v == value)?;
+        }
+        Some(self.state_of(indices.into_iter()))
+    }
+
+    // Same state, but with one of the properties changed, if the block has it
+    pub fn with_value(&self, state: usize, name: &str, value: &str) -> usize {
+        let Some(property) = self.0.iter().position(|property| property.name == name) else {
+            return state;
+        };
+        let Some(value) = self.0[property].values.iter().position(|v| v == value) else {
+            return state;
+        };
+        let mut indices: Vec<usize> = self.value_indices(state).collect();
+        indices[property] = value;
+        self.state_of(indices.into_iter())
+    }
+
+    // State of the block placed by the player, some of the common properties follow the placement:
+    // facing points at the player, half sticks to the clicked part of the face
+    // and axis goes along the face normal
+    pub fn placed_state(&self, placement: &Placement) -> usize {
+        let look = placement.look_direction;
+        let facing = if look.x.abs() > look.z.abs() {
+            if look.x > 0.0 {
+                "west"
+            } else {
+                "east"
+            }
+        } else if look.z > 0.0 {
+            "north"
+        } else {
+            "south"
+        };
+        let normal = placement.normal;
+        let half = if normal.y < 0.0 || (normal.y == 0.0 && placement.point.y.rem_euclid(1.0) > 0.5)
+        {
+            "top"
+        } else {
+            "bottom"
+        };
+        let axis = if normal.x != 0.0 {
+            "x"
+        } else if normal.z != 0.0 {
+            "z"
+        } else {
+            "y"
+        };
+
+        let state = self.with_value(0, "facing", facing);
+        let state = self.with_value(state, "half", half);
+        self.with_value(state, "axis", axis)
+    }
+}
+
+// Block state written as "namespace:block[property=value,property=value]",
+// block without properties is just its id
+pub fn format_state<'a>(id: &str, values: impl Iterator<Item = (&'a str, &'a str)>) -> String {
+    let values: Vec<String> = values
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    if values.is_empty() {
+        String::from(id)
+    } else {
+        format!("{}[{}]", id, values.join(","))
+    }
+}
+
+// Reverse of format_state, None if the string is malformed
+pub fn parse_state(state: &str) -> Option<(&str, Vec<(&str, &str)>)> {
+    let Some((id, values)) = state.split_once('[') else {
+        return Some((state, Vec::new()));
+    };
+    let values = values.strip_suffix(']')?;
+    let values = values
+        .split(',')
+        .filter(|value| !value.is_empty())
+        .map(|value| value.split_once('='))
+        .collect::<Option<Vec<_>>>()?;
+    Some((id, values))
+}
+
+#[cfg(test)]
+impl BlockProperties {
+    // Properties of a stair block, shared by tests of everything that depends on states
+    pub fn test_stairs() -> BlockProperties {
+        BlockProperties(vec![
+            BlockProperty {
+                name: String::from("facing"),
+                values: ["north", "south", "east", "west"]
+                    .map(String::from)
+                    .to_vec(),
+            },
+            BlockProperty {
+                name: String::from("half"),
+                values: ["bottom", "top"].map(String::from).to_vec(),
+            },
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_combination_is_a_state() {
+        let properties = BlockProperties::test_stairs();
+        assert_eq!(properties.state_count(), 8);
+        assert_eq!(BlockProperties::default().state_count(), 1);
+        let mut seen = Vec::new();
+        for state in 0..properties.state_count() {
+            let values: Vec<_> = properties.values(state).collect();
+            assert_eq!(properties.state(values.iter().copied()), Some(state));
+            assert!(!seen.contains(&values));
+            seen.push(values);
+        }
+    }
+
+    #[test]
+    fn missing_properties_are_default() {
+        let properties = BlockProperties::test_stairs();
+        assert_eq!(properties.state([]), Some(0));
+        let state = properties.state([("half", "top")]).unwrap();
+        assert_eq!(properties.value(state, "facing"), Some("north"));
+        assert_eq!(properties.value(state, "half"), Some("top"));
+        assert_eq!(properties.state([("half", "middle")]), None);
+        assert_eq!(properties.state([("color", "red")]), None);
+    }
+
+    #[test]
+    fn with_value_changes_one_property() {
+        let properties = BlockProperties::test_stairs();
+        let state = properties
+            .state([("facing", "east"), ("half", "top")])
+            .unwrap();
+        let changed = properties.with_value(state, "facing", "west");
+        assert_eq!(properties.value(changed, "facing"), Some("west"));
+        assert_eq!(properties.value(changed, "half"), Some("top"));
+        assert_eq!(properties.with_value(state, "axis", "x"), state);
+    }
+
+    #[test]
+    fn state_names_round_trip() {
+        let properties = BlockProperties::test_stairs();
+        let state = properties
+            .state([("facing", "west"), ("half", "top")])
+            .unwrap();
+        let name = format_state("mineclone:stairs", properties.values(state));
+        assert_eq!(name, "mineclone:stairs[facing=west,half=top]");
+        let (id, values) = parse_state(&name).unwrap();
+        assert_eq!(id, "mineclone:stairs");
+        assert_eq!(properties.state(values), Some(state));
+        assert_eq!(
+            parse_state("mineclone:stone"),
+            Some(("mineclone:stone", Vec::new()))
+        );
+        assert_eq!(parse_state("mineclone:stairs[facing"), None);
+    }
+}
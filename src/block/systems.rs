@@ -3,6 +3,7 @@ use std::marker::PhantomData;
 
 use super::{Block, BlockInfoFolder, BlocksTexturesFolder};
 use crate::{
+    chunk::material::{ChunkMaterial, TiledAtlas},
     common::{create_texture_atlas, AppState, Atlas, SetupState},
     registry::BlockRegistry,
 };
@@ -30,16 +31,38 @@ pub fn stitch_blocks_texture_atlas(
     loaded_folders: Res<Assets<LoadedFolder>>,
     mut textures: ResMut<Assets<Image>>,
     mut texture_layouts: ResMut<Assets<TextureAtlasLayout>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut materials: ResMut<Assets<ChunkMaterial>>,
 ) {
     let folder = loaded_folders.get(&blocks_textures.0).unwrap();
     let (atlas_layout, atlas_texture) =
         create_texture_atlas(folder, None, Some(ImageSampler::nearest()), &mut textures);
-    let atlas_layout = texture_layouts.add(atlas_layout);
-    let material_h = materials.add(StandardMaterial {
-        base_color_texture: Some(atlas_texture.clone()),
-        ..default()
+    // chunk material repeats textures in tiles of the same size,
+    // so all block textures must be the same size
+    let tile_size = atlas_layout
+        .textures
+        .first()
+        .map(|rect| rect.size())
+        .unwrap_or_default();
+    if atlas_layout
+        .textures
+        .iter()
+        .any(|rect| rect.size() != tile_size)
+    {
+        warn!("Block textures have different sizes, some of them will be drawn incorrectly");
+    }
+    let material_h = materials.add(ChunkMaterial {
+        base: StandardMaterial {
+            base_color_texture: Some(atlas_texture.clone()),
+            // transparent parts of the textures are cut out, plants need that
+            alpha_mode: AlphaMode::Mask(0.5),
+            ..default()
+        },
+        extension: TiledAtlas {
+            tile_size: tile_size / atlas_layout.size,
+            sky_light: 1.0,
+        },
     });
+    let atlas_layout = texture_layouts.add(atlas_layout);
     commands.insert_resource(Atlas {
         texture: atlas_texture,
         layout: atlas_layout,
@@ -73,6 +96,7 @@ pub fn populate_block_registry(
     mut registry: ResMut<BlockRegistry>,
 ) {
     let folder = loaded_folders.get(&blocks_info.0).unwrap();
+    let mut loaded_blocks = Vec::with_capacity(folder.handles.len());
     for handle in folder.handles.iter() {
         let handle = handle.clone().typed_unchecked::<Block>();
         let id = handle.id();
@@ -83,7 +107,25 @@ pub fn populate_block_registry(
             );
             continue;
         };
-        registry.register(block.id.clone(), handle);
+        loaded_blocks.push((block, handle));
+    }
+    // sorting, so numeric ids don't depend on the order files were loaded in
+    loaded_blocks.sort_by(|(a, _), (b, _)| a.id.0.cmp(&b.id.0));
+    for (block, handle) in loaded_blocks {
+        let numeric_id = match registry.register(handle, block) {
+            Ok(numeric_id) => numeric_id,
+            Err(e) => {
+                error!("Could not register block {}: {}", block.name, e);
+                continue;
+            }
+        };
+        info!(
+            "Registered block {} as {:?}({:?}) with {} states",
+            block.name,
+            block.id,
+            numeric_id,
+            block.properties.state_count()
+        );
     }
     // Dropping the handle to the blocks texture folder
     // since we have already stitched the atlas
@@ -33,17 +33,11 @@ impl CameraPerspective {
 #[derive(Component)]
 pub struct PlayerCamera {
     pub sensitivity: f32,
-    pub velocity: Vec3,
-    pub friction: f32,
 }
 
 impl Default for PlayerCamera {
     fn default() -> Self {
-        PlayerCamera {
-            sensitivity: 8.0,
-            velocity: Vec3::ZERO,
-            friction: 0.7,
-        }
+        PlayerCamera { sensitivity: 8.0 }
     }
 }
 
@@ -0,0 +1,232 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::{storage::BlockStorage, Chunk, ChunkDimensions};
+use crate::{
+    block::{Block, BLOCK_HALF_SIZE},
+    registry::BlockRegistry,
+};
+
+// Collision boxes of the chunk blocks, built from the block data instead of the render mesh
+#[derive(Clone, Debug)]
+pub struct ChunkCollider {
+    // same indices as in the chunk
+    block_data: BlockStorage,
+    // collision of every block in the chunk palette, 0 is air
+    palette: Vec<BlockCollision>,
+    dimensions: ChunkDimensions,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum BlockCollision {
+    // nothing to collide with
+    Empty,
+    // whole block, these are merged with each other
+    Full,
+    // corners of the boxes inside of the block, from 0 to 1
+    Boxes(Vec<(Vec3, Vec3)>),
+}
+
+impl ChunkCollider {
+    pub fn new(
+        chunk: &Chunk,
+        dimensions: ChunkDimensions,
+        registry: &BlockRegistry,
+        blocks: &Assets<Block>,
+    ) -> ChunkCollider {
+        let palette = std::iter::once(BlockCollision::Empty)
+            .chain(chunk.unique_blocks.iter().map(|block_state| {
+                let Some((block, state)) = registry.block_asset(*block_state, blocks) else {
+                    return BlockCollision::Empty;
+                };
+                BlockCollision::from(block.collision_boxes(state))
+            }))
+            .collect();
+        ChunkCollider {
+            block_data: chunk.block_data.clone(),
+            palette,
+            dimensions,
+        }
+    }
+
+    fn collision_at(&self, x: usize, y: usize, z: usize) -> &BlockCollision {
+        let index =
+            x * self.dimensions.width * self.dimensions.height + y * self.dimensions.width + z;
+        &self.palette[self.block_data.get(index) as usize]
+    }
+
+    // Position of the block corner with the lowest coordinates,
+    // relative to the center of the chunk
+    fn block_min_corner(&self, x: usize, y: usize, z: usize) -> Vec3 {
+        Vec3::new(
+            (x as isize - (self.dimensions.width / 2) as isize) as f32,
+            (y as isize - (self.dimensions.height / 2) as isize) as f32,
+            (z as isize - (self.dimensions.depth / 2) as isize) as f32,
+        ) * BLOCK_HALF_SIZE
+            * 2.0
+    }
+
+    // Corners of the boxes relative to the center of the chunk.
+    // Full blocks are merged into boxes as big as possible: first along z,
+    // then whole rows along y and whole layers along x, other blocks keep their own boxes
+    fn boxes(&self) -> Vec<(Vec3, Vec3)> {
+        let (width, height, depth) = (
+            self.dimensions.width,
+            self.dimensions.height,
+            self.dimensions.depth,
+        );
+        let index = |x: usize, y: usize, z: usize| x * width * height + y * width + z;
+        let mut merged = vec![false; width * height * depth];
+        let is_free = |merged: &[bool], x, y, z| {
+            !merged[index(x, y, z)] && *self.collision_at(x, y, z) == BlockCollision::Full
+        };
+
+        let block_size = BLOCK_HALF_SIZE * 2.0;
+        let mut boxes = Vec::new();
+        for x in 0..width {
+            for y in 0..height {
+                for z in 0..depth {
+                    match self.collision_at(x, y, z) {
+                        BlockCollision::Empty => continue,
+                        BlockCollision::Boxes(block_boxes) => {
+                            let corner = self.block_min_corner(x, y, z);
+                            boxes.extend(block_boxes.iter().map(|(min, max)| {
+                                (corner + *min * block_size, corner + *max * block_size)
+                            }));
+                            continue;
+                        }
+                        BlockCollision::Full if merged[index(x, y, z)] => continue,
+                        BlockCollision::Full => (),
+                    }
+
+                    let mut size_z = 1;
+                    while z + size_z < depth && is_free(&merged, x, y, z + size_z) {
+                        size_z += 1;
+                    }
+                    let mut size_y = 1;
+                    while y + size_y < height
+                        && (z..z + size_z).all(|k| is_free(&merged, x, y + size_y, k))
+                    {
+                        size_y += 1;
+                    }
+                    let mut size_x = 1;
+                    while x + size_x < width
+                        && (y..y + size_y)
+                            .all(|j| (z..z + size_z).all(|k| is_free(&merged, x + size_x, j, k)))
+                    {
+                        size_x += 1;
+                    }
+
+                    for i in x..x + size_x {
+                        for j in y..y + size_y {
+                            for k in z..z + size_z {
+                                merged[index(i, j, k)] = true;
+                            }
+                        }
+                    }
+                    let min = self.block_min_corner(x, y, z);
+                    let size = Vec3::new(size_x as f32, size_y as f32, size_z as f32);
+                    boxes.push((min, min + size * block_size));
+                }
+            }
+        }
+        boxes
+    }
+
+    // None if the chunk has nothing to collide with
+    pub fn build(&self) -> Option<Collider> {
+        let shapes: Vec<(Vect, Rot, Collider)> = self
+            .boxes()
+            .into_iter()
+            .map(|(min, max)| {
+                let half_size = (max - min) / 2.0;
+                (
+                    min + half_size,
+                    Rot::IDENTITY,
+                    Collider::cuboid(half_size.x, half_size.y, half_size.z),
+                )
+            })
+            .collect();
+        if shapes.is_empty() {
+            None
+        } else {
+            Some(Collider::compound(shapes))
+        }
+    }
+}
+
+impl From<Vec<(Vec3, Vec3)>> for BlockCollision {
+    fn from(boxes: Vec<(Vec3, Vec3)>) -> Self {
+        if boxes.is_empty() {
+            BlockCollision::Empty
+        } else if boxes == [(Vec3::ZERO, Vec3::ONE)] {
+            BlockCollision::Full
+        } else {
+            BlockCollision::Boxes(boxes)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::test_utils::{block_data, DIMS};
+
+    // Palette: 0 air, 1 full block, 2 bottom slab, 3 plant
+    fn chunk_collider<F>(block_at: F) -> ChunkCollider
+    where
+        F: Fn(usize, usize, usize) -> u16,
+    {
+        ChunkCollider {
+            block_data: block_data(block_at),
+            palette: vec![
+                BlockCollision::Empty,
+                BlockCollision::Full,
+                BlockCollision::Boxes(vec![(Vec3::ZERO, Vec3::new(1.0, 0.5, 1.0))]),
+                BlockCollision::Empty,
+            ],
+            dimensions: DIMS,
+        }
+    }
+
+    fn volume(boxes: &[(Vec3, Vec3)]) -> f32 {
+        boxes
+            .iter()
+            .map(|(min, max)| (*max - *min).to_array().iter().product::<f32>())
+            .sum()
+    }
+
+    #[test]
+    fn full_blocks_are_merged() {
+        let full = chunk_collider(|_, _, _| 1).boxes();
+        assert_eq!(full, vec![(Vec3::splat(-8.0), Vec3::splat(8.0))]);
+
+        let ground = chunk_collider(|_, y, _| (y < 5) as u16).boxes();
+        assert_eq!(ground, vec![(Vec3::splat(-8.0), Vec3::new(8.0, -3.0, 8.0))]);
+
+        // nothing touches, so nothing can be merged
+        let checkerboard = chunk_collider(|x, y, z| ((x + y + z) % 2) as u16).boxes();
+        assert_eq!(checkerboard.len(), 16 * 16 * 16 / 2);
+        assert_eq!(volume(&checkerboard), (16 * 16 * 16 / 2) as f32);
+    }
+
+    #[test]
+    fn partial_blocks_keep_their_boxes() {
+        let blocks = |x, y, z| match (x, y, z) {
+            (0, 0, 0) => 2,
+            (1, 0, 0) => 3,
+            (2, 0, 0) | (3, 0, 0) => 1,
+            _ => 0,
+        };
+        let mut boxes = chunk_collider(blocks).boxes();
+        boxes.sort_by(|(a, _), (b, _)| a.x.total_cmp(&b.x));
+        assert_eq!(
+            boxes,
+            vec![
+                (Vec3::splat(-8.0), Vec3::new(-7.0, -7.5, -7.0)),
+                (Vec3::new(-6.0, -8.0, -8.0), Vec3::new(-4.0, -7.0, -7.0)),
+            ]
+        );
+        assert!(chunk_collider(|_, _, _| 3).build().is_none());
+    }
+}
@@ -0,0 +1,33 @@
+use bevy::{
+    pbr::{ExtendedMaterial, MaterialExtension},
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef},
+};
+
+// Material chunks are rendered with, block atlas on top of the standard pbr material
+pub type ChunkMaterial = ExtendedMaterial<StandardMaterial, TiledAtlas>;
+
+// Lets one quad repeat the same block texture of the atlas multiple times,
+// so merged faces of greedy meshing still show one texture per block.
+// Meshes must have uv_0 in blocks(texture repeats every 1.0)
+// and uv_1 as the corner of the block texture in the atlas.
+// Vertex colors hold the brightness of sky light in red and of block light in green
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+pub struct TiledAtlas {
+    // size of one block texture in atlas uv coordinates
+    #[uniform(100)]
+    pub tile_size: Vec2,
+    // how bright sky light is at the current time of day, from 0 to 1
+    #[uniform(100)]
+    pub sky_light: f32,
+}
+
+impl MaterialExtension for TiledAtlas {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/chunk.wgsl".into()
+    }
+
+    fn deferred_fragment_shader() -> ShaderRef {
+        "shaders/chunk.wgsl".into()
+    }
+}
@@ -1,88 +1,479 @@
 use bevy::{
     prelude::*,
     render::{mesh::Indices, render_asset::RenderAssetUsages},
-    utils::HashMap,
 };
 
-use super::{Chunk, ChunkDimensions};
+use super::{storage::BlockStorage, Chunk, ChunkDimensions};
 use crate::{
-    block::{Block, BlockMesh, Opacity, BLOCK_HALF_SIZE},
+    block::{model::BlockShape, Block, BlockMesh, BlockTextures, Opacity, BLOCK_HALF_SIZE},
+    config::ChunkConfig,
     registry::BlockRegistry,
+    world::light::{brightness, LightKind, MAX_LIGHT},
 };
 
 #[derive(Clone, Debug)]
 pub struct ChunkMesh {
-    block_data: Vec<Option<BlockMesh>>,
+    // same indices as in the chunk
+    block_data: BlockStorage,
+    // mesh info of every block in the chunk palette, 0 is air
+    palette: Vec<Option<BlockMesh>>,
+    // whether blocks of the neighbouring chunks that touch this chunk hide faces of its blocks,
+    // one layer for every neighbour in Face::ALL order
+    borders: [Vec<bool>; 6],
+    // sky and block light levels of the chunk's blocks and of the one block thick layer
+    // of all 26 neighbours around it, see padded_index
+    padded_light: Vec<[u8; 2]>,
+    // whether the blocks are opaque cubes, they darken the corners of the faces next to them,
+    // same indices as padded_light
+    padded_solid: Vec<bool>,
     atlas_size: Vec2,
     dimensions: ChunkDimensions,
+    mesher: Mesher,
+    ambient_occlusion: bool,
+}
+
+// Where the block next to the face is, coordinates are local to the chunk
+enum Cell {
+    Inside(usize, usize, usize),
+    // index in the border layer of the neighbour
+    Border(Face, usize),
+}
+
+// How bright the corner is depending on the number of opaque blocks around it,
+// from the corner in between two of them to the open one
+const AMBIENT_OCCLUSION: [f32; 4] = [0.5, 0.65, 0.8, 1.0];
+
+// How visible faces of the blocks are turned into quads
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mesher {
+    // one quad for every visible face, only used to check greedy meshing against
+    #[cfg(test)]
+    Naive,
+    // coplanar neighbouring faces with the same texture are merged into bigger quads
+    #[default]
+    Greedy,
 }
 
 impl ChunkMesh {
+    // chunks must be in ChunkTranslation::with_surrounding order, the meshed one is in the middle
     pub fn new(
-        chunk: &Chunk,
+        chunks: [&Chunk; 27],
         chunk_dimensions: ChunkDimensions,
         atlas: &TextureAtlasLayout,
         registry: &Res<BlockRegistry>,
         blocks: &Res<Assets<Block>>,
+        chunk_config: &ChunkConfig,
     ) -> ChunkMesh {
-        let mut block_meshes = HashMap::with_capacity(chunk.unique_blocks.len());
-        for block_id in chunk.unique_blocks.iter() {
-            let handle = registry.get(block_id).unwrap();
-            let block = blocks.get(handle).unwrap();
-            block_meshes.insert(
-                block_id.clone(),
-                BlockMesh {
-                    opacity: block.opacity.clone(),
-                    textures: block
-                        .textures
-                        .clone()
-                        .map(|v| atlas.textures[atlas.get_texture_index(v).unwrap()]),
-                },
-            );
+        let chunk = chunks[surrounding_index(IVec3::ZERO)];
+        let neighbours = Face::ALL.map(|face| chunks[surrounding_index(face.normal())]);
+        let mut palette = Vec::with_capacity(chunk.unique_blocks.len() + 1);
+        palette.push(None);
+        for block_state in chunk.unique_blocks.iter() {
+            let (block, state) = registry.block_asset(*block_state, blocks).unwrap();
+            let textures = block
+                .textures_of(state)
+                .clone()
+                .map(|v| atlas.textures[atlas.get_texture_index(v).unwrap()]);
+            palette.push(Some(BlockMesh::new(
+                block.opacity.clone(),
+                textures,
+                block.model.shape(&block.properties, state),
+            )));
+        }
+
+        let borders = std::array::from_fn(|i| {
+            border_layer(
+                Face::ALL[i],
+                neighbours[i],
+                chunk_dimensions,
+                registry,
+                blocks,
+            )
+        });
+        // solid blocks in the palettes of the chunks
+        let solid: Vec<Vec<bool>> = chunks
+            .iter()
+            .map(|chunk| solid_palette(chunk, registry, blocks))
+            .collect();
+        let size = IVec3::new(
+            chunk_dimensions.width as i32,
+            chunk_dimensions.height as i32,
+            chunk_dimensions.depth as i32,
+        );
+        let padded_len = (size + 2).to_array().iter().product::<i32>() as usize;
+        let mut padded_light = Vec::with_capacity(padded_len);
+        let mut padded_solid = Vec::with_capacity(padded_len);
+        for x in -1..=size.x {
+            for y in -1..=size.y {
+                for z in -1..=size.z {
+                    let pos = IVec3::new(x, y, z);
+                    let i = surrounding_index(pos.div_euclid(size));
+                    let local = pos.rem_euclid(size).as_uvec3();
+                    let index = local.x as usize * chunk_dimensions.width * chunk_dimensions.height
+                        + local.y as usize * chunk_dimensions.width
+                        + local.z as usize;
+                    padded_light.push(LightKind::ALL.map(|kind| chunks[i].light.get(index, kind)));
+                    padded_solid.push(solid[i][chunks[i].block_data.get(index) as usize]);
+                }
+            }
         }
+
         ChunkMesh {
             dimensions: chunk_dimensions,
             atlas_size: atlas.size,
-            block_data: chunk
-                .block_data
-                .iter()
-                .map(|some_id| {
-                    some_id
-                        .as_ref()
-                        .and_then(|block_id| block_meshes.get(block_id).cloned())
-                })
-                .collect(),
+            block_data: chunk.block_data.clone(),
+            palette,
+            borders,
+            padded_light,
+            padded_solid,
+            mesher: chunk_config.mesher,
+            ambient_occlusion: chunk_config.ambient_occlusion,
         }
     }
 
-    fn get_block_at(&self, pos: &Vec3) -> Option<BlockMesh> {
-        let index = (pos.x as isize + (self.dimensions.width / 2) as isize)
-            * (self.dimensions.width as isize)
-            * (self.dimensions.height as isize)
-            + (pos.y as isize + (self.dimensions.height / 2) as isize)
-                * (self.dimensions.width as isize)
-            + (pos.z as isize + (self.dimensions.depth / 2) as isize);
-        self.block_data[index as usize].clone()
+    fn get_block_at(&self, x: usize, y: usize, z: usize) -> Option<&BlockMesh> {
+        let index =
+            x * self.dimensions.width * self.dimensions.height + y * self.dimensions.width + z;
+        self.palette[self.block_data.get(index) as usize].as_ref()
     }
 
-    fn get_block_opacity_at(&self, pos: &Vec3) -> Opacity {
-        let index = (pos.x as isize + (self.dimensions.width / 2) as isize)
-            * (self.dimensions.width as isize)
-            * (self.dimensions.height as isize)
-            + (pos.y as isize + (self.dimensions.height / 2) as isize)
-                * (self.dimensions.width as isize)
-            + (pos.z as isize + (self.dimensions.depth / 2) as isize);
-        let block = self.block_data[index as usize].clone();
+    // Index of the block in padded_light and padded_solid, local coordinates of the block
+    // can be one block outside of the chunk along any of the axes
+    fn padded_index(&self, pos: IVec3) -> Option<usize> {
+        let size = IVec3::new(
+            self.dimensions.width as i32,
+            self.dimensions.height as i32,
+            self.dimensions.depth as i32,
+        ) + 2;
+        let pos = pos + 1;
+        if pos.cmplt(IVec3::ZERO).any() || pos.cmpge(size).any() {
+            return None;
+        }
+        Some(((pos.x * size.y + pos.y) * size.z + pos.z) as usize)
+    }
 
-        if let Some(block) = block {
-            block.opacity
-        } else {
-            Opacity::Transparent(255)
+    // Takes local coordinates of the block, which can be one block outside of the chunk,
+    // None for blocks outside of the chunk along more than one axis, borders don't have them
+    fn cell(&self, pos: IVec3) -> Option<Cell> {
+        let size = IVec3::new(
+            self.dimensions.width as i32,
+            self.dimensions.height as i32,
+            self.dimensions.depth as i32,
+        );
+        let outside = Face::ALL.into_iter().filter(|face| {
+            let (n, _, _) = face.axes();
+            if face.normal()[n] > 0 {
+                pos[n] >= size[n]
+            } else {
+                pos[n] < 0
+            }
+        });
+        let mut outside = outside.take(2);
+        let Some(face) = outside.next() else {
+            let pos = pos.as_uvec3();
+            return Some(Cell::Inside(pos.x as usize, pos.y as usize, pos.z as usize));
+        };
+        if outside.next().is_some() {
+            return None;
+        }
+        let pos = pos.rem_euclid(size).as_uvec3();
+        let index = border_index(
+            face,
+            pos.x as usize,
+            pos.y as usize,
+            pos.z as usize,
+            self.dimensions,
+        );
+        Some(Cell::Border(face, index))
+    }
+
+    // Whether the block hides faces of the neighbour touching its side,
+    // borders only know about the sides facing this chunk
+    fn occludes_at(&self, pos: IVec3, side: Face) -> bool {
+        match self.cell(pos) {
+            Some(Cell::Inside(x, y, z)) => self
+                .get_block_at(x, y, z)
+                .is_some_and(|block| block.occluding_sides[side as usize]),
+            Some(Cell::Border(face, index)) => self.borders[face as usize][index],
+            None => false,
+        }
+    }
+
+    // Whether the block is an opaque cube, which casts ambient occlusion
+    fn solid_at(&self, pos: IVec3) -> bool {
+        self.padded_index(pos)
+            .is_some_and(|index| self.padded_solid[index])
+    }
+
+    fn light_at(&self, pos: IVec3) -> Option<[u8; 2]> {
+        Some(self.padded_light[self.padded_index(pos)?])
+    }
+
+    // Face on the side of the block is lit by the light in front of it
+    fn face_light(&self, face: Face, x: usize, y: usize, z: usize) -> [u8; 2] {
+        let pos = IVec3::new(x as i32, y as i32, z as i32);
+        self.light_at(pos + face.normal()).unwrap_or_default()
+    }
+
+    // Brightness of the face corners in the Quad::shade order.
+    // Every corner averages the light of the four blocks in front of the face that touch it
+    // and gets darker the more of them are opaque, as long as ambient occlusion is on
+    fn face_shade(&self, face: Face, x: usize, y: usize, z: usize) -> [Vec2; 4] {
+        let light = self.face_light(face, x, y, z);
+        if !self.ambient_occlusion {
+            return [light_brightness(light); 4];
+        }
+        let (_, a, b) = face.axes();
+        let front = IVec3::new(x as i32, y as i32, z as i32) + face.normal();
+        std::array::from_fn(|corner| {
+            let mut along_a = IVec3::ZERO;
+            along_a[a] = if corner & 1 == 1 { 1 } else { -1 };
+            let mut along_b = IVec3::ZERO;
+            along_b[b] = if corner & 2 == 2 { 1 } else { -1 };
+            let side_a = front + along_a;
+            let side_b = front + along_b;
+            let diagonal = front + along_a + along_b;
+
+            let (solid_a, solid_b) = (self.solid_at(side_a), self.solid_at(side_b));
+            // light can't get to the diagonal block through two opaque ones
+            let solid_diagonal = (solid_a && solid_b) || self.solid_at(diagonal);
+            let occlusion = if solid_a && solid_b {
+                0
+            } else {
+                3 - solid_a as usize - solid_b as usize - solid_diagonal as usize
+            };
+            let lit: Vec<Vec2> = [
+                (side_a, solid_a),
+                (side_b, solid_b),
+                (diagonal, solid_diagonal),
+            ]
+            .into_iter()
+            .filter(|(_, solid)| !solid)
+            .map(|(pos, _)| light_brightness(self.light_at(pos).unwrap_or(light)))
+            .chain(std::iter::once(light_brightness(light)))
+            .collect();
+            let average = lit.iter().sum::<Vec2>() / lit.len() as f32;
+            average * AMBIENT_OCCLUSION[occlusion]
+        })
+    }
+
+    // Face on the side of the block is hidden if the neighbour covers it with an opaque side,
+    // even if that neighbour is in the neighbouring chunk
+    fn is_face_hidden(&self, face: Face, x: usize, y: usize, z: usize) -> bool {
+        let pos = IVec3::new(x as i32, y as i32, z as i32);
+        self.occludes_at(pos + face.normal(), face.opposite())
+    }
+
+    // Texture of the full cube face, if the face is visible
+    fn visible_face(&self, face: Face, x: usize, y: usize, z: usize) -> Option<Rect> {
+        // if current block is air, there is nothing to draw,
+        // other shapes are meshed separately
+        let block = self.get_block_at(x, y, z).filter(|block| block.is_cube)?;
+        if self.is_face_hidden(face, x, y, z) {
+            return None;
+        }
+        Some(face.texture(&block.textures))
+    }
+
+    // Position of the block corner with the lowest coordinates,
+    // relative to the center of the chunk
+    fn block_min_corner(&self, x: usize, y: usize, z: usize) -> Vec3 {
+        Vec3::new(
+            (x as isize - (self.dimensions.width / 2) as isize) as f32,
+            (y as isize - (self.dimensions.height / 2) as isize) as f32,
+            (z as isize - (self.dimensions.depth / 2) as isize) as f32,
+        ) * BLOCK_HALF_SIZE
+            * 2.0
+    }
+
+    fn quads(&self) -> Vec<Quad> {
+        match self.mesher {
+            #[cfg(test)]
+            Mesher::Naive => self.naive_quads(),
+            Mesher::Greedy => self.greedy_quads(),
+        }
+    }
+
+    #[cfg(test)]
+    fn naive_quads(&self) -> Vec<Quad> {
+        let mut quads = Vec::new();
+        for x in 0..self.dimensions.width {
+            for y in 0..self.dimensions.height {
+                for z in 0..self.dimensions.depth {
+                    for face in Face::ALL {
+                        let Some(texture) = self.visible_face(face, x, y, z) else {
+                            continue;
+                        };
+                        let min = self.block_min_corner(x, y, z);
+                        quads.push(Quad::tiled(
+                            face,
+                            min,
+                            min + Vec3::splat(BLOCK_HALF_SIZE * 2.0),
+                            texture,
+                            self.face_shade(face, x, y, z),
+                        ));
+                    }
+                }
+            }
+        }
+        quads
+    }
+
+    // Goes slice by slice along the normal of every face and merges visible faces
+    // of the slice into rectangles: first as far as possible along one axis of the slice,
+    // then whole rows along the other one. Merged faces must have the same texture
+    // and the same brightness in all of their corners, so shading doesn't stretch over the quad
+    fn greedy_quads(&self) -> Vec<Quad> {
+        let dims = [
+            self.dimensions.width,
+            self.dimensions.height,
+            self.dimensions.depth,
+        ];
+        let mut quads = Vec::new();
+        for face in Face::ALL {
+            // axis along the face normal and two axes of the slice
+            let (n, a, b) = face.axes();
+            let block_pos = |s: usize, i: usize, j: usize| {
+                let mut pos = [0; 3];
+                pos[n] = s;
+                pos[a] = i;
+                pos[b] = j;
+                pos
+            };
+
+            for s in 0..dims[n] {
+                let mut mask = Vec::with_capacity(dims[a] * dims[b]);
+                for i in 0..dims[a] {
+                    for j in 0..dims[b] {
+                        let [x, y, z] = block_pos(s, i, j);
+                        mask.push(
+                            self.visible_face(face, x, y, z)
+                                .map(|texture| (texture, self.face_shade(face, x, y, z))),
+                        );
+                    }
+                }
+
+                for i in 0..dims[a] {
+                    let mut j = 0;
+                    while j < dims[b] {
+                        let Some((texture, shade)) = mask[i * dims[b] + j] else {
+                            j += 1;
+                            continue;
+                        };
+                        let cell = Some((texture, shade));
+                        // faces shaded unevenly are never merged
+                        let is_even = shade.iter().all(|corner| *corner == shade[0]);
+                        let mut width = 1;
+                        while is_even
+                            && j + width < dims[b]
+                            && mask[i * dims[b] + j + width] == cell
+                        {
+                            width += 1;
+                        }
+                        let mut height = 1;
+                        while is_even
+                            && i + height < dims[a]
+                            && (j..j + width).all(|k| mask[(i + height) * dims[b] + k] == cell)
+                        {
+                            height += 1;
+                        }
+                        for row in i..i + height {
+                            for cell in &mut mask[row * dims[b] + j..row * dims[b] + j + width] {
+                                *cell = None;
+                            }
+                        }
+
+                        let [x, y, z] = block_pos(s, i, j);
+                        let [max_x, max_y, max_z] = block_pos(s, i + height - 1, j + width - 1);
+                        quads.push(Quad::tiled(
+                            face,
+                            self.block_min_corner(x, y, z),
+                            self.block_min_corner(max_x, max_y, max_z)
+                                + Vec3::splat(BLOCK_HALF_SIZE * 2.0),
+                            texture,
+                            shade,
+                        ));
+                        j += width;
+                    }
+                }
+            }
+        }
+        quads
+    }
+
+    // Faces of the blocks that are not full cubes, they are never merged
+    fn model_faces(&self) -> Vec<[QuadVertex; 4]> {
+        let mut faces = Vec::new();
+        if self.palette.iter().flatten().all(|block| block.is_cube) {
+            return faces;
+        }
+        for x in 0..self.dimensions.width {
+            for y in 0..self.dimensions.height {
+                for z in 0..self.dimensions.depth {
+                    let Some(block) = self.get_block_at(x, y, z).filter(|block| !block.is_cube)
+                    else {
+                        continue;
+                    };
+                    faces.extend(shape_faces(
+                        &block.shape,
+                        &block.textures,
+                        self.block_min_corner(x, y, z),
+                        self.atlas_size,
+                        |face| self.is_face_hidden(face, x, y, z),
+                        |side| match side {
+                            Some(face) => self.face_light(face, x, y, z),
+                            None => self
+                                .light_at(IVec3::new(x as i32, y as i32, z as i32))
+                                .unwrap_or_default(),
+                        },
+                    ));
+                }
+            }
+        }
+        faces
+    }
+}
+
+// Rectangle covering one or more block faces with the same texture
+#[derive(Clone, Debug, PartialEq)]
+struct Quad {
+    face: Face,
+    // corners of the box made of blocks whose faces are covered,
+    // the quad lays on the side of this box
+    min: Vec3,
+    max: Vec3,
+    texture: Rect,
+    // uv of the quad corners, min is the top left one,
+    // the texture repeats every 1.0
+    uv: Rect,
+    // brightness of the corners, sky light in x and block light in y,
+    // first one has the lowest coordinates,
+    // the second one goes along the first axis of the face plane and the third along the second
+    shade: [Vec2; 4],
+}
+
+impl Quad {
+    // Quad repeating the whole texture once per block
+    fn tiled(face: Face, min: Vec3, max: Vec3, texture: Rect, shade: [Vec2; 4]) -> Quad {
+        let size = (max - min) / (BLOCK_HALF_SIZE * 2.0);
+        let (width, height) = match face {
+            Face::Front | Face::Back => (size.x, size.y),
+            Face::Right | Face::Left => (size.z, size.y),
+            Face::Top | Face::Bottom => (size.x, size.z),
+        };
+        Quad {
+            face,
+            min,
+            max,
+            texture,
+            uv: Rect::new(0.0, 0.0, width, height),
+            shade,
         }
     }
 }
 
-enum Face {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Face {
     Front,
     Back,
     Right,
@@ -91,234 +482,822 @@ enum Face {
     Bottom,
 }
 
-fn get_face_mesh(
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::Front,
+        Face::Back,
+        Face::Right,
+        Face::Left,
+        Face::Top,
+        Face::Bottom,
+    ];
+
+    pub fn opposite(&self) -> Face {
+        match self {
+            Face::Front => Face::Back,
+            Face::Back => Face::Front,
+            Face::Right => Face::Left,
+            Face::Left => Face::Right,
+            Face::Top => Face::Bottom,
+            Face::Bottom => Face::Top,
+        }
+    }
+
+    // Axis along the normal and two axes of the plane of the face
+    pub fn axes(&self) -> (usize, usize, usize) {
+        let n = match self {
+            Face::Right | Face::Left => 0,
+            Face::Top | Face::Bottom => 1,
+            Face::Front | Face::Back => 2,
+        };
+        (n, (n + 1) % 3, (n + 2) % 3)
+    }
+
+    // Direction the face is looking at
+    pub fn normal(&self) -> IVec3 {
+        match self {
+            Face::Front => IVec3::Z,
+            Face::Back => IVec3::NEG_Z,
+            Face::Right => IVec3::X,
+            Face::Left => IVec3::NEG_X,
+            Face::Top => IVec3::Y,
+            Face::Bottom => IVec3::NEG_Y,
+        }
+    }
+
+    fn texture<T: Copy>(&self, textures: &BlockTextures<T>) -> T {
+        match self {
+            Face::Front => *textures.back(),
+            Face::Back => *textures.front(),
+            Face::Right => *textures.right(),
+            Face::Left => *textures.left(),
+            Face::Top => *textures.top(),
+            Face::Bottom => *textures.bottom(),
+        }
+    }
+}
+
+// Index in the border layer of the face, layer has no coordinate along the face normal
+fn border_index(face: Face, x: usize, y: usize, z: usize, dimensions: ChunkDimensions) -> usize {
+    match face {
+        Face::Right | Face::Left => y * dimensions.depth + z,
+        Face::Top | Face::Bottom => x * dimensions.depth + z,
+        Face::Front | Face::Back => x * dimensions.height + y,
+    }
+}
+
+// Collects opacity of the neighbour's blocks that touch the chunk from the side of the face
+fn border_layer(
     face: Face,
-    pos: Vec3,
-    atlas_size: Vec2,
-    uv: Rect,
-) -> [([f32; 3], [f32; 3], [f32; 2]); 4] {
-    let min = pos;
-    let max = pos + Vec3::splat(BLOCK_HALF_SIZE * 2.0);
-    let leftx = uv.min.x / atlas_size.x;
-    let rightx = uv.max.x / atlas_size.x;
-    let boty = uv.min.y / atlas_size.y;
-    let topy = uv.max.y / atlas_size.y;
+    neighbour: &Chunk,
+    dimensions: ChunkDimensions,
+    registry: &BlockRegistry,
+    blocks: &Assets<Block>,
+) -> Vec<bool> {
+    // neighbour's blocks touch the chunk with their opposite side
+    let side = face.opposite();
+    let occludes: Vec<bool> = std::iter::once(false)
+        .chain(neighbour.unique_blocks.iter().map(|block_state| {
+            registry
+                .block_asset(*block_state, blocks)
+                .is_some_and(|(block, state)| {
+                    block.opacity == Opacity::Opaque
+                        && block.model.shape(&block.properties, state).covers(side)
+                })
+        }))
+        .collect();
+
+    neighbour_layer(face, dimensions, |index| {
+        occludes[neighbour.block_data.get(index) as usize]
+    })
+}
+
+// Which blocks in the palette of the chunk are opaque cubes, 0 is air
+fn solid_palette(chunk: &Chunk, registry: &BlockRegistry, blocks: &Assets<Block>) -> Vec<bool> {
+    std::iter::once(false)
+        .chain(chunk.unique_blocks.iter().map(|block_state| {
+            registry
+                .block_asset(*block_state, blocks)
+                .is_some_and(|(block, state)| {
+                    block.opacity == Opacity::Opaque
+                        && block.model.shape(&block.properties, state).is_cube()
+                })
+        }))
+        .collect()
+}
+
+// Index in the ChunkTranslation::with_surrounding array of the chunk at the offset
+fn surrounding_index(offset: IVec3) -> usize {
+    let offset = (offset + 1).as_uvec3();
+    (offset.x * 9 + offset.y * 3 + offset.z) as usize
+}
+
+// Goes through the neighbour's blocks touching the chunk from the side of the face,
+// value_at takes the index of the block in the neighbour
+fn neighbour_layer<T, F>(face: Face, dimensions: ChunkDimensions, value_at: F) -> Vec<T>
+where
+    T: Clone + Default,
+    F: Fn(usize) -> T,
+{
+    // neighbour's blocks touching the chunk lay on the opposite side of the neighbour
+    let (xs, ys, zs) = match face {
+        Face::Right => (0..1, 0..dimensions.height, 0..dimensions.depth),
+        Face::Left => (
+            dimensions.width - 1..dimensions.width,
+            0..dimensions.height,
+            0..dimensions.depth,
+        ),
+        Face::Top => (0..dimensions.width, 0..1, 0..dimensions.depth),
+        Face::Bottom => (
+            0..dimensions.width,
+            dimensions.height - 1..dimensions.height,
+            0..dimensions.depth,
+        ),
+        Face::Front => (0..dimensions.width, 0..dimensions.height, 0..1),
+        Face::Back => (
+            0..dimensions.width,
+            0..dimensions.height,
+            dimensions.depth - 1..dimensions.depth,
+        ),
+    };
+    let layer_size = match face {
+        Face::Right | Face::Left => dimensions.height * dimensions.depth,
+        Face::Top | Face::Bottom => dimensions.width * dimensions.depth,
+        Face::Front | Face::Back => dimensions.width * dimensions.height,
+    };
+    let mut layer = vec![T::default(); layer_size];
+    for x in xs {
+        for y in ys.clone() {
+            for z in zs.clone() {
+                let index = x * dimensions.width * dimensions.height + y * dimensions.width + z;
+                layer[border_index(face, x, y, z, dimensions)] = value_at(index);
+            }
+        }
+    }
+    layer
+}
+
+// position, normal, uv, atlas uv of the texture corner and color
+type QuadVertex = ([f32; 3], [f32; 3], [f32; 2], [f32; 2], [f32; 4]);
+
+// Sky and block light go into separate channels of the color,
+// the shader dims only the sky one at night
+fn shade_color(shade: Vec2) -> [f32; 4] {
+    [shade.x, shade.y, 0.0, 1.0]
+}
+
+// Brightness of both light kinds, sky light in x and block light in y
+fn light_brightness(light: [u8; 2]) -> Vec2 {
+    Vec2::from_array(light.map(brightness))
+}
+
+fn get_face_mesh(quad: &Quad, atlas_size: Vec2) -> [QuadVertex; 4] {
+    let Quad {
+        face,
+        min,
+        max,
+        texture,
+        uv,
+        shade,
+    } = *quad;
+    let leftx = uv.min.x;
+    let rightx = uv.max.x;
+    // y axis of the image goes down, so the top of the texture is at 0
+    let boty = uv.max.y;
+    let topy = uv.min.y;
+    let corner = (texture.min / atlas_size).to_array();
+    let (_, a, b) = face.axes();
     // Truthfully stolen from bevy cuboid Meshable instance :)
     // Suppose Y-up right hand, and camera look from +Z to -Z
-    match face {
+    let mut vertices = match face {
         Face::Front => [
-            ([min.x, min.y, max.z], [0.0, 0.0, 1.0], [leftx, boty]),
-            ([max.x, min.y, max.z], [0.0, 0.0, 1.0], [rightx, boty]),
-            ([max.x, max.y, max.z], [0.0, 0.0, 1.0], [rightx, topy]),
-            ([min.x, max.y, max.z], [0.0, 0.0, 1.0], [leftx, topy]),
+            (
+                [min.x, min.y, max.z],
+                [0.0, 0.0, 1.0],
+                [leftx, boty],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [max.x, min.y, max.z],
+                [0.0, 0.0, 1.0],
+                [rightx, boty],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [max.x, max.y, max.z],
+                [0.0, 0.0, 1.0],
+                [rightx, topy],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [min.x, max.y, max.z],
+                [0.0, 0.0, 1.0],
+                [leftx, topy],
+                corner,
+                [0.0; 4],
+            ),
         ],
         Face::Back => [
-            ([min.x, max.y, min.z], [0.0, 0.0, -1.0], [leftx, topy]),
-            ([max.x, max.y, min.z], [0.0, 0.0, -1.0], [rightx, topy]),
-            ([max.x, min.y, min.z], [0.0, 0.0, -1.0], [rightx, boty]),
-            ([min.x, min.y, min.z], [0.0, 0.0, -1.0], [leftx, boty]),
+            (
+                [min.x, max.y, min.z],
+                [0.0, 0.0, -1.0],
+                [leftx, topy],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [max.x, max.y, min.z],
+                [0.0, 0.0, -1.0],
+                [rightx, topy],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [max.x, min.y, min.z],
+                [0.0, 0.0, -1.0],
+                [rightx, boty],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [min.x, min.y, min.z],
+                [0.0, 0.0, -1.0],
+                [leftx, boty],
+                corner,
+                [0.0; 4],
+            ),
         ],
         Face::Right => [
-            ([max.x, min.y, min.z], [1.0, 0.0, 0.0], [leftx, boty]),
-            ([max.x, max.y, min.z], [1.0, 0.0, 0.0], [leftx, topy]),
-            ([max.x, max.y, max.z], [1.0, 0.0, 0.0], [rightx, topy]),
-            ([max.x, min.y, max.z], [1.0, 0.0, 0.0], [rightx, boty]),
+            (
+                [max.x, min.y, min.z],
+                [1.0, 0.0, 0.0],
+                [leftx, boty],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [max.x, max.y, min.z],
+                [1.0, 0.0, 0.0],
+                [leftx, topy],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [max.x, max.y, max.z],
+                [1.0, 0.0, 0.0],
+                [rightx, topy],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [max.x, min.y, max.z],
+                [1.0, 0.0, 0.0],
+                [rightx, boty],
+                corner,
+                [0.0; 4],
+            ),
         ],
         Face::Left => [
-            ([min.x, min.y, max.z], [-1.0, 0.0, 0.0], [leftx, boty]),
-            ([min.x, max.y, max.z], [-1.0, 0.0, 0.0], [leftx, topy]),
-            ([min.x, max.y, min.z], [-1.0, 0.0, 0.0], [rightx, topy]),
-            ([min.x, min.y, min.z], [-1.0, 0.0, 0.0], [rightx, boty]),
+            (
+                [min.x, min.y, max.z],
+                [-1.0, 0.0, 0.0],
+                [leftx, boty],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [min.x, max.y, max.z],
+                [-1.0, 0.0, 0.0],
+                [leftx, topy],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [min.x, max.y, min.z],
+                [-1.0, 0.0, 0.0],
+                [rightx, topy],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [min.x, min.y, min.z],
+                [-1.0, 0.0, 0.0],
+                [rightx, boty],
+                corner,
+                [0.0; 4],
+            ),
         ],
         Face::Top => [
-            ([max.x, max.y, min.z], [0.0, 1.0, 0.0], [rightx, boty]),
-            ([min.x, max.y, min.z], [0.0, 1.0, 0.0], [leftx, boty]),
-            ([min.x, max.y, max.z], [0.0, 1.0, 0.0], [leftx, topy]),
-            ([max.x, max.y, max.z], [0.0, 1.0, 0.0], [rightx, topy]),
+            (
+                [max.x, max.y, min.z],
+                [0.0, 1.0, 0.0],
+                [rightx, boty],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [min.x, max.y, min.z],
+                [0.0, 1.0, 0.0],
+                [leftx, boty],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [min.x, max.y, max.z],
+                [0.0, 1.0, 0.0],
+                [leftx, topy],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [max.x, max.y, max.z],
+                [0.0, 1.0, 0.0],
+                [rightx, topy],
+                corner,
+                [0.0; 4],
+            ),
         ],
         Face::Bottom => [
-            ([max.x, min.y, max.z], [0.0, -1.0, 0.0], [rightx, boty]),
-            ([min.x, min.y, max.z], [0.0, -1.0, 0.0], [leftx, boty]),
-            ([min.x, min.y, min.z], [0.0, -1.0, 0.0], [leftx, topy]),
-            ([max.x, min.y, min.z], [0.0, -1.0, 0.0], [rightx, topy]),
+            (
+                [max.x, min.y, max.z],
+                [0.0, -1.0, 0.0],
+                [rightx, boty],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [min.x, min.y, max.z],
+                [0.0, -1.0, 0.0],
+                [leftx, boty],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [min.x, min.y, min.z],
+                [0.0, -1.0, 0.0],
+                [leftx, topy],
+                corner,
+                [0.0; 4],
+            ),
+            (
+                [max.x, min.y, min.z],
+                [0.0, -1.0, 0.0],
+                [rightx, topy],
+                corner,
+                [0.0; 4],
+            ),
         ],
+    };
+    // every vertex gets the shade of the corner it's in
+    for (position, _, _, _, color) in vertices.iter_mut() {
+        let position = Vec3::from_array(*position);
+        let corner = (position[a] == max[a]) as usize + 2 * (position[b] == max[b]) as usize;
+        *color = shade_color(shade[corner]);
+    }
+    vertices
+}
+
+// Faces of the block shape, origin is the block corner with the lowest coordinates.
+// Faces on the sides of the block are skipped, if they are hidden by the neighbours.
+// light takes the side of the block the face is on, None for faces inside of the block
+fn shape_faces<F, L>(
+    shape: &BlockShape,
+    textures: &BlockTextures<Rect>,
+    origin: Vec3,
+    atlas_size: Vec2,
+    is_hidden: F,
+    light: L,
+) -> Vec<[QuadVertex; 4]>
+where
+    F: Fn(Face) -> bool,
+    L: Fn(Option<Face>) -> [u8; 2],
+{
+    let block_size = BLOCK_HALF_SIZE * 2.0;
+    let boxes = match shape {
+        BlockShape::Boxes(boxes) => boxes,
+        BlockShape::Cross => return cross_faces(textures, origin, atlas_size, light(None)),
+    };
+    let mut faces = Vec::new();
+    for shape_box in boxes {
+        for face in Face::ALL {
+            let (n, _, _) = face.axes();
+            let on_side = if face.normal()[n] > 0 {
+                shape_box.max[n] == 1.0
+            } else {
+                shape_box.min[n] == 0.0
+            };
+            if on_side && is_hidden(face) {
+                continue;
+            }
+            let quad = Quad {
+                face,
+                min: origin + shape_box.min * block_size,
+                max: origin + shape_box.max * block_size,
+                texture: face.texture(textures),
+                uv: shape_box.uvs[face as usize],
+                shade: [light_brightness(light(on_side.then_some(face))); 4],
+            };
+            faces.push(get_face_mesh(&quad, atlas_size));
+        }
     }
+    faces
+}
+
+// Two diagonal planes through the block, drawn from both sides
+fn cross_faces(
+    textures: &BlockTextures<Rect>,
+    origin: Vec3,
+    atlas_size: Vec2,
+    light: [u8; 2],
+) -> Vec<[QuadVertex; 4]> {
+    let block_size = BLOCK_HALF_SIZE * 2.0;
+    let corner = (Face::Front.texture(textures).min / atlas_size).to_array();
+    let color = shade_color(light_brightness(light));
+    let up = Vec3::Y * block_size;
+    // bottom left and bottom right corners of every plane looking from its front
+    [
+        (Vec3::ZERO, Vec3::new(1.0, 0.0, 1.0)),
+        (Vec3::new(1.0, 0.0, 1.0), Vec3::ZERO),
+        (Vec3::Z, Vec3::X),
+        (Vec3::X, Vec3::Z),
+    ]
+    .into_iter()
+    .map(|(left, right)| {
+        let left = origin + left * block_size;
+        let right = origin + right * block_size;
+        let normal = (right - left).cross(Vec3::Y).normalize().to_array();
+        [
+            (left.to_array(), normal, [0.0, 1.0], corner, color),
+            (right.to_array(), normal, [1.0, 1.0], corner, color),
+            ((right + up).to_array(), normal, [1.0, 0.0], corner, color),
+            ((left + up).to_array(), normal, [0.0, 0.0], corner, color),
+        ]
+    })
+    .collect()
 }
 
 impl Meshable for ChunkMesh {
     type Output = Mesh;
 
     fn mesh(&self) -> Self::Output {
-        // keeps track of curent index for indices
-        let mut indice = 0;
-        let mut indices = Vec::new();
-        let mut vertices = Vec::new();
-        for x in 0..self.dimensions.width {
-            for y in 0..self.dimensions.height {
-                for z in 0..self.dimensions.depth {
-                    // this is a position of block in the chunk(center of the block)
-                    let pos = Vec3::new(
-                        (x as isize - (self.dimensions.width / 2) as isize) as f32,
-                        (y as isize - (self.dimensions.height / 2) as isize) as f32,
-                        (z as isize - (self.dimensions.depth / 2) as isize) as f32,
-                    );
-                    // if current block is air, we don't need to do anything
-                    if self.get_block_at(&pos).is_none() {
-                        continue;
-                    }
-                    // if we're at the front chunk border or there is no block in front
-                    if z == 0
-                        || (z > 0 && self.get_block_opacity_at(&(pos - Vec3::Z)) != Opacity::Opaque)
-                    {
-                        if let Some(block) = self.get_block_at(&pos) {
-                            vertices.push(get_face_mesh(
-                                Face::Back,
-                                pos * BLOCK_HALF_SIZE * 2.0,
-                                self.atlas_size,
-                                *block.textures.front(),
-                            ));
-                            indices.extend_from_slice(&[
-                                indice,
-                                indice + 1,
-                                indice + 2,
-                                indice + 2,
-                                indice + 3,
-                                indice,
-                            ]);
-                            indice += 4;
-                        }
-                    }
-                    // if we're at the back chunk border or there is no block before
-                    if (z == self.dimensions.depth - 1)
-                        || (z < self.dimensions.depth - 1
-                            && self.get_block_opacity_at(&(pos + Vec3::Z)) != Opacity::Opaque)
-                    {
-                        if let Some(block) = self.get_block_at(&pos) {
-                            vertices.push(get_face_mesh(
-                                Face::Front,
-                                pos * BLOCK_HALF_SIZE * 2.0,
-                                self.atlas_size,
-                                *block.textures.back(),
-                            ));
-                            indices.extend_from_slice(&[
-                                indice,
-                                indice + 1,
-                                indice + 2,
-                                indice + 2,
-                                indice + 3,
-                                indice,
-                            ]);
-                            indice += 4;
-                        }
-                    }
-                    // if we're at the left chunk border or there is no block on the left
-                    if x == 0
-                        || (x > 0 && self.get_block_opacity_at(&(pos - Vec3::X)) != Opacity::Opaque)
-                    {
-                        if let Some(block) = self.get_block_at(&pos) {
-                            vertices.push(get_face_mesh(
-                                Face::Left,
-                                pos * BLOCK_HALF_SIZE * 2.0,
-                                self.atlas_size,
-                                *block.textures.left(),
-                            ));
-                            indices.extend_from_slice(&[
-                                indice,
-                                indice + 1,
-                                indice + 2,
-                                indice + 2,
-                                indice + 3,
-                                indice,
-                            ]);
-                            indice += 4;
-                        }
-                    }
+        let mut faces: Vec<_> = self
+            .quads()
+            .iter()
+            .map(|quad| get_face_mesh(quad, self.atlas_size))
+            .collect();
+        faces.extend(self.model_faces());
+        faces_mesh(faces)
+    }
+}
 
-                    // if we're at the right chunk border or there is no block on the right
-                    if (x == self.dimensions.width - 1)
-                        || (x < self.dimensions.width - 1
-                            && self.get_block_opacity_at(&(pos + Vec3::X)) != Opacity::Opaque)
-                    {
-                        if let Some(block) = self.get_block_at(&pos) {
-                            vertices.push(get_face_mesh(
-                                Face::Right,
-                                pos * BLOCK_HALF_SIZE * 2.0,
-                                self.atlas_size,
-                                *block.textures.right(),
-                            ));
-                            indices.extend_from_slice(&[
-                                indice,
-                                indice + 1,
-                                indice + 2,
-                                indice + 2,
-                                indice + 3,
-                                indice,
-                            ]);
-                            indice += 4;
-                        }
-                    }
+// Single block centered at the origin, for blocks that are not part of a chunk
+pub struct BlockItemMesh {
+    pub textures: BlockTextures<Rect>,
+    pub shape: BlockShape,
+    pub atlas_size: Vec2,
+}
 
-                    // if we're at the bottom chunk border or there is no block beneath
-                    if (y == 0)
-                        || (y > 0 && self.get_block_opacity_at(&(pos - Vec3::Y)) != Opacity::Opaque)
-                    {
-                        if let Some(block) = self.get_block_at(&pos) {
-                            vertices.push(get_face_mesh(
-                                Face::Bottom,
-                                pos * BLOCK_HALF_SIZE * 2.0,
-                                self.atlas_size,
-                                *block.textures.bottom(),
-                            ));
-                            indices.extend_from_slice(&[
-                                indice,
-                                indice + 1,
-                                indice + 2,
-                                indice + 2,
-                                indice + 3,
-                                indice,
-                            ]);
-                            indice += 4;
-                        }
-                    }
+impl Meshable for BlockItemMesh {
+    type Output = Mesh;
 
-                    // if we're at the top chunk border or there is no block at the top
-                    if (y == self.dimensions.height - 1)
-                        || (y < self.dimensions.height - 1
-                            && self.get_block_opacity_at(&(pos + Vec3::Y)) != Opacity::Opaque)
-                    {
-                        if let Some(block) = self.get_block_at(&pos) {
-                            vertices.push(get_face_mesh(
-                                Face::Top,
-                                pos * BLOCK_HALF_SIZE * 2.0,
-                                self.atlas_size,
-                                *block.textures.top(),
-                            ));
-                            indices.extend_from_slice(&[
-                                indice,
-                                indice + 1,
-                                indice + 2,
-                                indice + 2,
-                                indice + 3,
-                                indice,
-                            ]);
-                            indice += 4;
-                        }
+    fn mesh(&self) -> Self::Output {
+        faces_mesh(shape_faces(
+            &self.shape,
+            &self.textures,
+            Vec3::splat(-BLOCK_HALF_SIZE),
+            self.atlas_size,
+            |_| false,
+            // items are not lit by the world
+            |_| [MAX_LIGHT; 2],
+        ))
+    }
+}
+
+fn faces_mesh(vertices: Vec<[QuadVertex; 4]>) -> Mesh {
+    // keeps track of curent index for indices
+    let mut indice = 0;
+    let mut indices = Vec::new();
+    for face in vertices.iter() {
+        // the brighter of both kinds, the way it looks during the day
+        let brightness = |i: usize| face[i].4[0].max(face[i].4[1]);
+        // quad is split along the diagonal with brighter corners,
+        // so a single dark corner darkens only one of the triangles
+        if brightness(0) + brightness(2) < brightness(1) + brightness(3) {
+            indices.extend_from_slice(&[
+                indice + 1,
+                indice + 2,
+                indice + 3,
+                indice + 3,
+                indice,
+                indice + 1,
+            ]);
+        } else {
+            indices.extend_from_slice(&[
+                indice,
+                indice + 1,
+                indice + 2,
+                indice + 2,
+                indice + 3,
+                indice,
+            ]);
+        }
+        indice += 4;
+    }
+    let positions: Vec<_> = vertices.iter().flatten().map(|v| v.0).collect();
+    let normals: Vec<_> = vertices.iter().flatten().map(|v| v.1).collect();
+    let uvs: Vec<_> = vertices.iter().flatten().map(|v| v.2).collect();
+    let corners: Vec<_> = vertices.iter().flatten().map(|v| v.3).collect();
+    let colors: Vec<_> = vertices.iter().flatten().map(|v| v.4).collect();
+    let indices = Indices::U32(indices);
+
+    Mesh::new(
+        bevy::render::render_resource::PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_1, corners)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+    .with_inserted_indices(indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{model::BlockModel, state::BlockProperties};
+    use crate::chunk::test_utils::{block_data, DIMS};
+
+    fn texture(i: f32) -> Rect {
+        Rect::new(i * 16.0, 0.0, (i + 1.0) * 16.0, 16.0)
+    }
+
+    // Palette: 0 air, 1 stone, 2 dirt, 3 grass, 4 stone slab
+    fn chunk_mesh<F>(mesher: Mesher, opaque_borders: bool, block_at: F) -> ChunkMesh
+    where
+        F: Fn(usize, usize, usize) -> u16,
+    {
+        let block = |textures| {
+            Some(BlockMesh::new(
+                Opacity::Opaque,
+                textures,
+                BlockShape::cube(),
+            ))
+        };
+        // stone, dirt and grass are solid, blocks of the neighbours are solid with opaque borders
+        let size = IVec3::new(DIMS.width as i32, DIMS.height as i32, DIMS.depth as i32);
+        let mut padded_solid = Vec::new();
+        for x in -1..=size.x {
+            for y in -1..=size.y {
+                for z in -1..=size.z {
+                    let pos = IVec3::new(x, y, z);
+                    padded_solid.push(if pos.cmplt(IVec3::ZERO).any() || pos.cmpge(size).any() {
+                        opaque_borders
+                    } else {
+                        let pos = pos.as_uvec3();
+                        (1..=3).contains(&block_at(pos.x as usize, pos.y as usize, pos.z as usize))
+                    });
+                }
+            }
+        }
+        ChunkMesh {
+            block_data: block_data(&block_at),
+            palette: vec![
+                None,
+                block(BlockTextures::Single(texture(0.0))),
+                block(BlockTextures::Single(texture(1.0))),
+                block(BlockTextures::TopBottomAndSide {
+                    top: texture(2.0),
+                    bottom: texture(1.0),
+                    side: texture(3.0),
+                }),
+                Some(BlockMesh::new(
+                    Opacity::Opaque,
+                    BlockTextures::Single(texture(0.0)),
+                    BlockModel::Slab.shape(&BlockProperties::default(), 0),
+                )),
+            ],
+            borders: std::array::from_fn(|_| vec![opaque_borders; DIMS.width * DIMS.height]),
+            padded_light: vec![[0; 2]; padded_solid.len()],
+            padded_solid,
+            atlas_size: Vec2::new(64.0, 16.0),
+            dimensions: DIMS,
+            mesher,
+            // shading keeps faces from merging, these tests are about the merging
+            ambient_occlusion: false,
+        }
+    }
+
+    fn triangles(chunk_mesh: &ChunkMesh) -> usize {
+        chunk_mesh.mesh().indices().map_or(0, |i| i.len() / 3)
+    }
+
+    // Every single block face covered by the quads, with its texture
+    fn coverage(chunk_mesh: &ChunkMesh) -> Vec<(usize, [i32; 3], [i32; 4])> {
+        let mut faces = Vec::new();
+        for quad in chunk_mesh.quads() {
+            let min = quad.min.as_ivec3();
+            let max = quad.max.as_ivec3();
+            for x in min.x..max.x {
+                for y in min.y..max.y {
+                    for z in min.z..max.z {
+                        faces.push((
+                            quad.face as usize,
+                            [x, y, z],
+                            [
+                                quad.texture.min.x as i32,
+                                quad.texture.min.y as i32,
+                                quad.texture.max.x as i32,
+                                quad.texture.max.y as i32,
+                            ],
+                        ));
                     }
                 }
             }
         }
-        let positions: Vec<_> = vertices.iter().flatten().map(|(p, _, _)| *p).collect();
-        let normals: Vec<_> = vertices.iter().flatten().map(|(_, n, _)| *n).collect();
-        let uvs: Vec<_> = vertices.iter().flatten().map(|(_, _, uv)| *uv).collect();
-        let indices = Indices::U32(indices);
-
-        Mesh::new(
-            bevy::render::render_resource::PrimitiveTopology::TriangleList,
-            RenderAssetUsages::default(),
-        )
-        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
-        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
-        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
-        .with_inserted_indices(indices)
+        faces.sort();
+        faces
+    }
+
+    // Deterministic "random" numbers, so tests don't need extra dependencies
+    fn hash(x: usize, y: usize, z: usize) -> usize {
+        let mut h = x.wrapping_mul(73856093) ^ y.wrapping_mul(19349663) ^ z.wrapping_mul(83492791);
+        h ^= h >> 13;
+        h.wrapping_mul(0x5bd1e995) >> 7
+    }
+
+    fn terrain(x: usize, y: usize, z: usize) -> u16 {
+        let height = 6 + (x / 4 + z / 5) % 4;
+        if y > height {
+            0
+        } else if y == height {
+            3
+        } else if y + 3 >= height {
+            2
+        } else if hash(x, y, z) % 7 == 0 {
+            // caves
+            0
+        } else {
+            1
+        }
+    }
+
+    fn checkerboard(x: usize, y: usize, z: usize) -> u16 {
+        ((x + y + z) % 2) as u16 * 2
+    }
+
+    fn random_blocks(x: usize, y: usize, z: usize) -> u16 {
+        (hash(x, y, z) % 4) as u16
+    }
+
+    type BlockAt = fn(usize, usize, usize) -> u16;
+
+    fn samples() -> Vec<(&'static str, BlockAt)> {
+        vec![
+            ("empty", |_, _, _| 0),
+            ("full", |_, _, _| 1),
+            ("single", |x, y, z| (x == 3 && y == 7 && z == 12) as u16 * 3),
+            ("terrain", terrain),
+            ("checkerboard", checkerboard),
+            ("random", random_blocks),
+        ]
+    }
+
+    #[test]
+    fn greedy_covers_same_faces_as_naive() {
+        for (name, block_at) in samples() {
+            for opaque_borders in [false, true] {
+                let naive = coverage(&chunk_mesh(Mesher::Naive, opaque_borders, block_at));
+                let greedy = coverage(&chunk_mesh(Mesher::Greedy, opaque_borders, block_at));
+                assert_eq!(naive, greedy, "coverage differs for {} chunk", name);
+            }
+        }
+    }
+
+    #[test]
+    fn greedy_never_has_more_triangles() {
+        for (name, block_at) in samples() {
+            for opaque_borders in [false, true] {
+                let naive = triangles(&chunk_mesh(Mesher::Naive, opaque_borders, block_at));
+                let greedy = triangles(&chunk_mesh(Mesher::Greedy, opaque_borders, block_at));
+                assert!(
+                    greedy <= naive,
+                    "{} chunk: greedy {} > naive {}",
+                    name,
+                    greedy,
+                    naive
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn triangle_counts() {
+        let full = |_, _, _| 1;
+        assert_eq!(
+            triangles(&chunk_mesh(Mesher::Naive, false, full)),
+            6 * 16 * 16 * 2
+        );
+        assert_eq!(triangles(&chunk_mesh(Mesher::Greedy, false, full)), 6 * 2);
+        // surrounded by opaque neighbours nothing is visible
+        assert_eq!(triangles(&chunk_mesh(Mesher::Naive, true, full)), 0);
+        assert_eq!(triangles(&chunk_mesh(Mesher::Greedy, true, full)), 0);
+
+        let single = |x, y, z| (x == 3 && y == 7 && z == 12) as u16 * 3;
+        assert_eq!(triangles(&chunk_mesh(Mesher::Naive, false, single)), 12);
+        assert_eq!(triangles(&chunk_mesh(Mesher::Greedy, false, single)), 12);
+
+        // nothing to merge, all neighbouring faces have different textures or are hidden
+        assert_eq!(
+            triangles(&chunk_mesh(Mesher::Greedy, false, checkerboard)),
+            triangles(&chunk_mesh(Mesher::Naive, false, checkerboard))
+        );
+
+        let naive = triangles(&chunk_mesh(Mesher::Naive, false, terrain));
+        let greedy = triangles(&chunk_mesh(Mesher::Greedy, false, terrain));
+        assert!(greedy * 2 < naive, "greedy {} naive {}", greedy, naive);
+    }
+
+    #[test]
+    fn merged_quad_repeats_texture_per_block() {
+        let slab = |_, y, _| (y == 0) as u16 * 2;
+        let quads = chunk_mesh(Mesher::Greedy, false, slab).quads();
+        let top = quads.iter().find(|quad| quad.face == Face::Top).unwrap();
+        let vertices = get_face_mesh(top, Vec2::new(64.0, 16.0));
+        for (_, _, uv, corner, _) in vertices {
+            assert!(uv[0] == 0.0 || uv[0] == 16.0);
+            assert!(uv[1] == 0.0 || uv[1] == 16.0);
+            assert_eq!(corner, [0.25, 0.0]);
+        }
+    }
+
+    #[test]
+    fn slab_hides_only_faces_it_covers() {
+        // slab lays on the stone, they hide the faces they touch with
+        let slab_on_stone = |x, y, z| match (x, y, z) {
+            (3, 7, 12) => 1,
+            (3, 8, 12) => 4,
+            _ => 0,
+        };
+        let mesh = chunk_mesh(Mesher::Greedy, false, slab_on_stone);
+        assert_eq!(triangles(&mesh), (5 + 5) * 2);
+
+        // top of the slab is in the middle of the block, so the stone above doesn't hide it,
+        // but the slab doesn't cover the whole bottom of the stone either
+        let stone_on_slab = |x, y, z| match (x, y, z) {
+            (3, 7, 12) => 4,
+            (3, 8, 12) => 1,
+            _ => 0,
+        };
+        let mesh = chunk_mesh(Mesher::Greedy, false, stone_on_slab);
+        assert_eq!(triangles(&mesh), (6 + 6) * 2);
+    }
+
+    #[test]
+    fn corners_next_to_walls_are_darker() {
+        // stone floor with a single block standing on it
+        let floor_and_block = |x, y, z| match (x, y, z) {
+            (_, 0, _) => 1,
+            (5, 1, 5) => 1,
+            _ => 0,
+        };
+        let mut mesh = chunk_mesh(Mesher::Greedy, false, floor_and_block);
+        mesh.ambient_occlusion = true;
+
+        // top of the floor goes along z and then x, the block is on the +x side of this face
+        let [min_z_min_x, max_z_min_x, min_z_max_x, max_z_max_x] =
+            mesh.face_shade(Face::Top, 4, 0, 5);
+        assert_eq!(min_z_min_x, max_z_min_x);
+        assert_eq!(min_z_max_x, max_z_max_x);
+        assert!(min_z_max_x.cmplt(min_z_min_x).all());
+        // corner touching the block only diagonally is darkened just as much
+        let [_, _, _, diagonal] = mesh.face_shade(Face::Top, 4, 0, 4);
+        assert_eq!(diagonal, min_z_max_x);
+        // far from the block nothing is darkened
+        let open = mesh.face_shade(Face::Top, 10, 0, 10);
+        assert!(open.iter().all(|corner| *corner == min_z_min_x));
+
+        mesh.ambient_occlusion = false;
+        let flat = mesh.face_shade(Face::Top, 4, 0, 5);
+        assert!(flat.iter().all(|corner| *corner == flat[0]));
+    }
+
+    #[test]
+    fn blocks_of_diagonal_neighbours_darken_corners() {
+        let floor = |_, y, _| (y == 0) as u16;
+        let mut mesh = chunk_mesh(Mesher::Greedy, false, floor);
+        mesh.ambient_occlusion = true;
+        let open = mesh.face_shade(Face::Top, 0, 0, 0);
+        assert!(open.iter().all(|corner| *corner == open[0]));
+
+        // block standing on the floor of the chunk next to both the -x and -z ones,
+        // it only touches the corner of the chunk
+        let index = mesh.padded_index(IVec3::new(-1, 1, -1)).unwrap();
+        mesh.padded_solid[index] = true;
+        let [corner, rest @ ..] = mesh.face_shade(Face::Top, 0, 0, 0);
+        assert!(rest.iter().all(|other| corner.cmplt(*other).all()));
     }
 }
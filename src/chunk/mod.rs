@@ -1,24 +1,38 @@
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
 
 use crate::{
-    block::BlockId,
+    block::state::BlockStateId,
     chunk::{
         debug::{show_chunk_border, toggle_show_chunks, ShowChunks},
+        material::ChunkMaterial,
+        storage::BlockStorage,
         systems::*,
+        tasks::ChunkTasks,
     },
     common::AppState,
+    ui::InInventory,
+    world::light::LightStorage,
 };
 
+pub mod collider;
 pub mod debug;
+pub mod material;
 pub mod mesh;
+pub mod storage;
 mod systems;
+pub mod tasks;
+#[cfg(test)]
+mod test_utils;
 
 pub struct ChunkPlugin;
 
 impl Plugin for ChunkPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(ChunkDimensions::default())
+        app.add_plugins(MaterialPlugin::<ChunkMaterial>::default())
+            .insert_resource(ChunkDimensions::default())
             .insert_resource(ShowChunks::DontShow)
+            .init_resource::<ChunkTasks>()
+            .init_resource::<ChunkEntities>()
             .add_event::<ChunkEvent>()
             .configure_sets(
                 Update,
@@ -32,14 +46,19 @@ impl Plugin for ChunkPlugin {
                     (show_chunk_border.run_if(resource_equals(ShowChunks::Show))),
                     (
                         mark_chunks,
-                        // create_object,
-                        destroy_object,
+                        // gameplay input is ignored while the inventory is open
+                        place_block.run_if(resource_equals(InInventory::Out)),
                         toggle_show_chunks,
                     )
                         .in_set(ChunkSystems::PlayerInput),
-                    (load_chunks, unload_chunks, reload_chunk)
-                        .in_set(ChunkSystems::ChunkReload)
-                        .run_if(on_event::<ChunkEvent>()),
+                    (
+                        (load_chunks, unload_chunks, reload_chunk).run_if(on_event::<ChunkEvent>()),
+                        reload_relit_chunks,
+                        start_chunk_tasks,
+                        finish_chunk_tasks,
+                    )
+                        .chain()
+                        .in_set(ChunkSystems::ChunkReload),
                 ),
             );
     }
@@ -56,38 +75,132 @@ pub struct ChunkMarker {
     pub translation: ChunkTranslation,
 }
 
+// Entities of all spawned chunks by their translation
+#[derive(Resource, Debug, Default)]
+pub struct ChunkEntities(pub HashMap<ChunkTranslation, Entity>);
+
+// Finds chunk entities without going through all of the chunks
+#[derive(SystemParam)]
+pub struct ChunkLookup<'w> {
+    entities: Res<'w, ChunkEntities>,
+    dimensions: Res<'w, ChunkDimensions>,
+}
+
+impl<'w> ChunkLookup<'w> {
+    pub fn get(&self, chunk_translation: ChunkTranslation) -> Option<Entity> {
+        self.entities.0.get(&chunk_translation).copied()
+    }
+
+    pub fn contains(&self, chunk_translation: ChunkTranslation) -> bool {
+        self.entities.0.contains_key(&chunk_translation)
+    }
+
+    // Entity of the chunk the block at the global position belongs to
+    pub fn at_block(&self, pos: Vec3) -> Option<Entity> {
+        self.get(ChunkTranslation::get_chunk_translation(
+            pos,
+            *self.dimensions,
+        ))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ChunkTranslation, Entity)> + '_ {
+        self.entities.0.iter().map(|(t, e)| (*t, *e))
+    }
+
+    // Remeshes the chunk of the changed block, neighbours need to be remeshed too,
+    // if the block was on their border
+    pub fn reload_chunks_sharing_block(
+        &self,
+        block_pos: Vec3,
+        chunk_ev: &mut EventWriter<ChunkEvent>,
+    ) {
+        for translation in ChunkTranslation::chunks_sharing_block(block_pos, *self.dimensions) {
+            if let Some(entity) = self.get(translation) {
+                chunk_ev.send(ChunkEvent::Reload(entity));
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Chunk {
-    pub block_data: Vec<Option<BlockId>>,
+    // indices into the palette, 0 is air and i is unique_blocks[i - 1]
+    pub block_data: BlockStorage,
     pub translation: ChunkTranslation,
-    pub unique_blocks: Vec<BlockId>,
+    // palette of the chunk, states of the blocks in it
+    pub unique_blocks: Vec<BlockStateId>,
+    // worked out every time the chunk is loaded, so it's never saved
+    pub light: LightStorage,
 }
 
 impl Chunk {
-    pub fn set_block_at(
-        &mut self,
-        pos: Vec3,
-        block_id: Option<BlockId>,
-        dimensions: ChunkDimensions,
-    ) -> Option<BlockId> {
-        let pos = Chunk::get_local_block_pos(pos, self.translation, dimensions);
+    // Chunk filled with air
+    pub fn new(translation: ChunkTranslation, dimensions: ChunkDimensions) -> Chunk {
+        Chunk {
+            block_data: BlockStorage::new(
+                dimensions.width * dimensions.height * dimensions.depth,
+                0,
+            ),
+            translation,
+            unique_blocks: Vec::new(),
+            light: LightStorage::new(dimensions.width * dimensions.height * dimensions.depth),
+        }
+    }
+
+    fn get_index(pos: Vec3, translation: ChunkTranslation, dimensions: ChunkDimensions) -> usize {
+        let pos = Chunk::get_local_block_pos(pos, translation, dimensions);
         let index = (pos.x as isize + (dimensions.width / 2) as isize)
             * (dimensions.width as isize)
             * (dimensions.height as isize)
             + (pos.y as isize + (dimensions.height / 2) as isize) * (dimensions.width as isize)
             + (pos.z as isize + (dimensions.depth / 2) as isize);
-        let res = self.block_data[index as usize].clone();
-        self.block_data[index as usize] = block_id;
+        index as usize
+    }
+
+    // Finds block in the palette, adding it if it's not there yet
+    fn get_palette_index(&mut self, block_state: Option<BlockStateId>) -> u16 {
+        let Some(block_state) = block_state else {
+            return 0;
+        };
+        match self
+            .unique_blocks
+            .iter()
+            .position(|state| *state == block_state)
+        {
+            Some(i) => i as u16 + 1,
+            None => {
+                self.unique_blocks.push(block_state);
+                self.unique_blocks.len() as u16
+            }
+        }
+    }
+
+    pub fn get_block_by_index(&self, index: usize) -> Option<BlockStateId> {
+        match self.block_data.get(index) {
+            0 => None,
+            i => self.unique_blocks.get(i as usize - 1).copied(),
+        }
+    }
+
+    pub fn set_block_by_index(&mut self, index: usize, block_state: Option<BlockStateId>) {
+        let palette_index = self.get_palette_index(block_state);
+        self.block_data.set(index, palette_index);
+    }
+
+    pub fn set_block_at(
+        &mut self,
+        pos: Vec3,
+        block_state: Option<BlockStateId>,
+        dimensions: ChunkDimensions,
+    ) -> Option<BlockStateId> {
+        let index = Chunk::get_index(pos, self.translation, dimensions);
+        let res = self.get_block_by_index(index);
+        self.set_block_by_index(index, block_state);
         res
     }
-    pub fn get_block_at(&self, pos: Vec3, dimensions: ChunkDimensions) -> Option<BlockId> {
-        let pos = Chunk::get_local_block_pos(pos, self.translation, dimensions);
-        let index = (pos.x as isize + (dimensions.width / 2) as isize)
-            * (dimensions.width as isize)
-            * (dimensions.height as isize)
-            + (pos.y as isize + (dimensions.height / 2) as isize) * (dimensions.width as isize)
-            + (pos.z as isize + (dimensions.depth / 2) as isize);
-        self.block_data[index as usize].clone()
+    pub fn get_block_at(&self, pos: Vec3, dimensions: ChunkDimensions) -> Option<BlockStateId> {
+        let index = Chunk::get_index(pos, self.translation, dimensions);
+        self.get_block_by_index(index)
     }
     pub fn get_local_block_pos(
         pos: Vec3,
@@ -106,6 +219,7 @@ impl Chunk {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
 pub struct ChunkLoadData {
     translation: ChunkTranslation,
     global_pos: Vec3,
@@ -155,4 +269,54 @@ impl ChunkTranslation {
             z: global_point.z.div_euclid(dimensions.depth as f32) as isize,
         }
     }
+
+    // Whether the chunk is inside the vertical cylinder around the center chunk,
+    // horizontal is the radius of the cylinder and vertical is half of its height, both in chunks
+    pub fn is_within(&self, center: ChunkTranslation, horizontal: isize, vertical: isize) -> bool {
+        let (dx, dz) = (self.x - center.x, self.z - center.z);
+        dx * dx + dz * dz <= horizontal * horizontal && (self.y - center.y).abs() <= vertical
+    }
+
+    // The chunk and every chunk touching it with a side, an edge or a corner,
+    // going along x, then y, then z, same as blocks in the chunk
+    pub fn with_surrounding(&self) -> [ChunkTranslation; 27] {
+        std::array::from_fn(|i| ChunkTranslation {
+            x: self.x + (i / 9) as isize - 1,
+            y: self.y + (i / 3 % 3) as isize - 1,
+            z: self.z + (i % 3) as isize - 1,
+        })
+    }
+
+    // Chunks whose meshes depend on the block at the global position,
+    // that is the chunk of the block and neighbours that share a side, an edge or a corner with it
+    pub fn chunks_sharing_block(pos: Vec3, dimensions: ChunkDimensions) -> Vec<ChunkTranslation> {
+        let translation = ChunkTranslation::get_chunk_translation(pos, dimensions);
+        let local = IVec3::new(
+            pos.x.floor() as i32 - (translation.x * dimensions.width as isize) as i32,
+            pos.y.floor() as i32 - (translation.y * dimensions.height as isize) as i32,
+            pos.z.floor() as i32 - (translation.z * dimensions.depth as isize) as i32,
+        );
+        let last = IVec3::new(
+            dimensions.width as i32 - 1,
+            dimensions.height as i32 - 1,
+            dimensions.depth as i32 - 1,
+        );
+        translation
+            .with_surrounding()
+            .into_iter()
+            .filter(|neighbour| {
+                let offset = IVec3::new(
+                    (neighbour.x - translation.x) as i32,
+                    (neighbour.y - translation.y) as i32,
+                    (neighbour.z - translation.z) as i32,
+                );
+                // the block is on the border with the neighbour along every axis it is moved on
+                (0..3).all(|axis| match offset[axis] {
+                    -1 => local[axis] == 0,
+                    1 => local[axis] == last[axis],
+                    _ => true,
+                })
+            })
+            .collect()
+    }
 }
@@ -0,0 +1,190 @@
+// Fixed size array of small numbers(palette indices), that uses as few bits per entry as possible
+// Entries never cross u64 boundaries, so some bits at the end of every word may stay unused
+#[derive(Debug, Clone)]
+pub enum BlockStorage {
+    // every entry has the same value, nothing is allocated
+    Single {
+        value: u16,
+        len: usize,
+    },
+    Packed {
+        bits: u32,
+        len: usize,
+        words: Vec<u64>,
+    },
+}
+
+// How many bits are needed to store the value, at least 1
+fn bits_for(value: u16) -> u32 {
+    (u16::BITS - value.leading_zeros()).max(1)
+}
+
+impl BlockStorage {
+    pub fn new(len: usize, value: u16) -> BlockStorage {
+        BlockStorage::Single { value, len }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            BlockStorage::Single { len, .. } => *len,
+            BlockStorage::Packed { len, .. } => *len,
+        }
+    }
+
+    pub fn bits(&self) -> u32 {
+        match self {
+            BlockStorage::Single { .. } => 0,
+            BlockStorage::Packed { bits, .. } => *bits,
+        }
+    }
+
+    pub fn get(&self, index: usize) -> u16 {
+        match self {
+            BlockStorage::Single { value, len } => {
+                assert!(index < *len, "index {} is out of bounds {}", index, len);
+                *value
+            }
+            BlockStorage::Packed { bits, len, words } => {
+                assert!(index < *len, "index {} is out of bounds {}", index, len);
+                let per_word = (u64::BITS / bits) as usize;
+                let shift = (index % per_word) as u32 * bits;
+                let mask = (1u64 << bits) - 1;
+                ((words[index / per_word] >> shift) & mask) as u16
+            }
+        }
+    }
+
+    pub fn set(&mut self, index: usize, value: u16) {
+        match self {
+            BlockStorage::Single {
+                value: current,
+                len,
+            } => {
+                if *current == value {
+                    return;
+                }
+                let bits = bits_for((*current).max(value));
+                *self = BlockStorage::packed(bits, *len, std::iter::repeat(*current));
+            }
+            BlockStorage::Packed { bits, .. } => {
+                if bits_for(value) > *bits {
+                    self.repack(bits_for(value));
+                }
+            }
+        }
+        if let BlockStorage::Packed { bits, len, words } = self {
+            assert!(index < *len, "index {} is out of bounds {}", index, len);
+            let per_word = (u64::BITS / *bits) as usize;
+            let shift = (index % per_word) as u32 * *bits;
+            let mask = ((1u64 << *bits) - 1) << shift;
+            let word = &mut words[index / per_word];
+            *word = (*word & !mask) | ((value as u64) << shift);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    // Switches to the single value representation if all entries are the same
+    // and shrinks bits per entry to fit the biggest value
+    pub fn compact(&mut self) {
+        let len = self.len();
+        let Some(first) = self.iter().next() else {
+            return;
+        };
+        if self.iter().all(|v| v == first) {
+            *self = BlockStorage::Single { value: first, len };
+            return;
+        }
+        let max = self.iter().max().unwrap_or_default();
+        if bits_for(max) < self.bits() {
+            self.repack(bits_for(max));
+        }
+    }
+
+    fn repack(&mut self, bits: u32) {
+        let len = self.len();
+        let values: Vec<u16> = self.iter().collect();
+        *self = BlockStorage::packed(bits, len, values.into_iter());
+    }
+
+    fn packed(bits: u32, len: usize, values: impl Iterator<Item = u16>) -> BlockStorage {
+        let per_word = (u64::BITS / bits) as usize;
+        let mut words = vec![0u64; len.div_ceil(per_word)];
+        for (index, value) in values.take(len).enumerate() {
+            let shift = (index % per_word) as u32 * bits;
+            words[index / per_word] |= (value as u64) << shift;
+        }
+        BlockStorage::Packed { bits, len, words }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_survive_growing_bit_width() {
+        let len = 4096;
+        let mut storage = BlockStorage::new(len, 0);
+        assert_eq!(storage.bits(), 0);
+        // every new value needs one more bit than the ones before it
+        let values = [1, 2, 4, 8, 16, 300, 40000];
+        for (i, value) in values.iter().enumerate() {
+            storage.set(i * 97, *value);
+        }
+        assert_eq!(storage.bits(), 16);
+        let expected = |index: usize| match values.get(index / 97) {
+            Some(value) if index / 97 * 97 == index => *value,
+            _ => 0,
+        };
+        assert!((0..len).all(|i| storage.get(i) == expected(i)));
+    }
+
+    #[test]
+    fn entries_next_to_word_boundaries_stay_intact() {
+        // 3 bits fit 21 times into a word, one bit of every word is left unused
+        let len = 100;
+        let value = |index: usize| (index % 7 + 1) as u16;
+        let mut storage = BlockStorage::new(len, 0);
+        storage.set(0, 4);
+        assert_eq!(storage.bits(), 3);
+        for index in 0..len {
+            storage.set(index, value(index));
+        }
+        for index in [20, 21, 41, 42, 62, 63, 99] {
+            assert_eq!(storage.get(index), value(index));
+        }
+        // overwriting an entry doesn't touch the ones next to it
+        storage.set(21, 0);
+        assert_eq!(storage.get(20), value(20));
+        assert_eq!(storage.get(21), 0);
+        assert_eq!(storage.get(22), value(22));
+    }
+
+    #[test]
+    fn compact_shrinks_storage() {
+        let mut storage = BlockStorage::new(64, 0);
+        storage.set(5, 200);
+        storage.set(6, 3);
+        storage.set(5, 1);
+        assert_eq!(storage.bits(), 8);
+        storage.compact();
+        assert_eq!(storage.bits(), 2);
+        assert_eq!(
+            storage.iter().filter(|v| *v != 0).collect::<Vec<_>>(),
+            [1, 3]
+        );
+        assert_eq!(storage.get(6), 3);
+
+        for index in 0..64 {
+            storage.set(index, 3);
+        }
+        storage.compact();
+        assert!(matches!(
+            storage,
+            BlockStorage::Single { value: 3, len: 64 }
+        ));
+    }
+}
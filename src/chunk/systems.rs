@@ -1,94 +1,78 @@
-use bevy::prelude::*;
+use std::sync::Arc;
+
+use bevy::{
+    prelude::*,
+    tasks::{block_on, poll_once, AsyncComputeTaskPool},
+};
 use bevy_rapier3d::prelude::*;
 
 use crate::{
-    block::Block,
+    block::{state::Placement, Block},
+    camera::PlayerCamera,
     common::Atlas,
     config::GameConfig,
+    game_mode::GameMode,
+    inventory::Inventory,
     player::{LookingAt, Player},
     registry::BlockRegistry,
     world::GameWorld,
 };
 
 use super::{
-    mesh::ChunkMesh, ChunkDimensions, ChunkEvent, ChunkLoadData, ChunkMarker, ChunkTranslation,
+    collider::ChunkCollider,
+    material::ChunkMaterial,
+    mesh::ChunkMesh,
+    tasks::{BuiltMesh, ChunkTasks, LoadPriority, MeshTarget},
+    ChunkDimensions, ChunkEntities, ChunkEvent, ChunkLoadData, ChunkLookup, ChunkMarker,
+    ChunkTranslation,
 };
 
+#[allow(clippy::too_many_arguments)]
 pub fn mark_chunks(
     config: Res<GameConfig>,
     chunk_dimensions: Res<ChunkDimensions>,
-    player_query: Query<&Transform, (With<Player>, Changed<Transform>)>,
+    player_query: Query<&Transform, With<Player>>,
     mut chunk_ev: EventWriter<ChunkEvent>,
-    chunk_query: Query<(Entity, &ChunkMarker)>,
+    chunk_lookup: ChunkLookup,
+    mut game_world: ResMut<GameWorld>,
+    block_registry: Res<BlockRegistry>,
+    mut chunk_tasks: ResMut<ChunkTasks>,
+    mut last_translation: Local<Option<ChunkTranslation>>,
 ) {
-    let render_dist = config.chunk_config.render_distance;
-    let chunk_translation = ChunkTranslation::get_chunk_translation(
-        player_query.single().translation,
-        *chunk_dimensions,
-    );
-
-    // these are coordinates of chunks(i.e. not real global coordinates)
-    let start_x = chunk_translation.x - render_dist as isize;
-    let end_x = chunk_translation.x + render_dist as isize;
-    let start_y = chunk_translation.y - render_dist as isize;
-    let end_y = chunk_translation.y + render_dist as isize;
-    let start_z = chunk_translation.z - render_dist as isize;
-    let end_z = chunk_translation.z + render_dist as isize;
+    let Ok(transform) = player_query.get_single() else {
+        return;
+    };
+    let chunk_translation =
+        ChunkTranslation::get_chunk_translation(transform.translation, *chunk_dimensions);
+    // loaded chunks only change when the player crosses into another chunk
+    if *last_translation == Some(chunk_translation) {
+        return;
+    }
+    *last_translation = Some(chunk_translation);
+    let horizontal = config.chunk_config.render_distance as isize;
+    let vertical = config.chunk_config.vertical_render_distance as isize;
+    let is_visible = |t: ChunkTranslation| t.is_within(chunk_translation, horizontal, vertical);
+    // chunks right outside of the render distance are kept, since they are needed for meshing,
+    // diagonal neighbours of the chunks on the edge of the circle can be up to 2 chunks away
+    let is_needed =
+        |t: ChunkTranslation| t.is_within(chunk_translation, horizontal + 2, vertical + 1);
+    chunk_tasks.cancel_stale(is_visible, is_needed);
+    if let Err(e) = game_world.retain_chunks(is_needed, &block_registry) {
+        error!("Could not save chunks: {}", e);
+    }
 
-    let mut chunks =
-        vec![vec![vec![false; 2 * render_dist + 1]; 2 * render_dist + 1]; 2 * render_dist + 1];
-    for (chunk_entity, chunk) in chunk_query.iter() {
-        if start_x <= chunk.translation.x
-            && chunk.translation.x <= end_x
-            && start_y <= chunk.translation.y
-            && chunk.translation.y <= end_y
-            && start_z <= chunk.translation.z
-            && chunk.translation.z <= end_z
-        {
-            // these are "normalized" chunk coordinates
-            let x = if start_x >= 0 {
-                chunk.translation.x - start_x.abs()
-            } else {
-                chunk.translation.x + start_x.abs()
-            };
-            let y = if start_y >= 0 {
-                chunk.translation.y - start_y.abs()
-            } else {
-                chunk.translation.y + start_y.abs()
-            };
-            let z = if start_z >= 0 {
-                chunk.translation.z - start_z.abs()
-            } else {
-                chunk.translation.z + start_z.abs()
-            };
-            chunks[x as usize][y as usize][z as usize] = true;
-        } else {
+    for (translation, chunk_entity) in chunk_lookup.iter() {
+        if !is_visible(translation) {
             chunk_ev.send(ChunkEvent::Remove(chunk_entity));
         }
     }
 
     // here we iterate on CHUNK coordinates(i.e. not global transform)
-    for x in start_x..=end_x {
-        for y in start_y..=end_y {
-            for z in start_z..=end_z {
-                // these are "normalized" chunk coordinates
-                let xi = if start_x >= 0 {
-                    x - start_x.abs()
-                } else {
-                    x + start_x.abs()
-                };
-                let yi = if start_y >= 0 {
-                    y - start_y.abs()
-                } else {
-                    y + start_y.abs()
-                };
-                let zi = if start_z >= 0 {
-                    z - start_z.abs()
-                } else {
-                    z + start_z.abs()
-                };
-                // checking if chunk was marked as non-existant
-                if !chunks[xi as usize][yi as usize][zi as usize] {
+    for x in chunk_translation.x - horizontal..=chunk_translation.x + horizontal {
+        for y in chunk_translation.y - vertical..=chunk_translation.y + vertical {
+            for z in chunk_translation.z - horizontal..=chunk_translation.z + horizontal {
+                let translation = ChunkTranslation { x, y, z };
+                if is_visible(translation) && !chunk_lookup.contains(translation) {
                     // global coordinates would equal to chunk translation coord * dimension
                     let x_global = {
                         let x = x as f32;
@@ -106,7 +90,7 @@ pub fn mark_chunks(
                         (z + 0.5) * depth
                     };
                     chunk_ev.send(ChunkEvent::Load(ChunkLoadData {
-                        translation: ChunkTranslation { x, y, z },
+                        translation,
                         global_pos: Vec3::new(x_global, y_global, z_global),
                     }));
                 }
@@ -115,102 +99,65 @@ pub fn mark_chunks(
     }
 }
 
-pub fn load_chunks(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut game_world: ResMut<GameWorld>,
-    // all chunks marked for loading
-    mut chunk_ev: EventReader<ChunkEvent>,
-    blocks: Res<Assets<Block>>,
-    block_registry: Res<BlockRegistry>,
-    block_atlas: Res<Atlas<Block>>,
-    layouts: Res<Assets<TextureAtlasLayout>>,
-) {
+pub fn load_chunks(mut chunk_ev: EventReader<ChunkEvent>, mut chunk_tasks: ResMut<ChunkTasks>) {
     for chunk_event in chunk_ev.read() {
         let chunk_data = match chunk_event {
             ChunkEvent::Load(chunk_data) => chunk_data,
             _ => continue,
         };
-
-        let chunk_dimensions = game_world.chunk_dimensions;
-        let chunk = game_world.get_chunk_at(chunk_data.translation);
-        let atlas_layout = layouts.get(&block_atlas.layout).unwrap();
-        let chunk_mesh = ChunkMesh::new(
-            chunk,
-            chunk_dimensions,
-            atlas_layout,
-            &block_registry,
-            &blocks,
-        );
-
-        let mesh = chunk_mesh.mesh();
-        // apparently Collider::from_bevy_mesh panics, because of
-        // assert!(indices.len() > 0), so I need to check it manually
-        // Why can't you rust return Err?
-        let collider = mesh.indices().and_then(|inds| {
-            if !inds.is_empty() {
-                Collider::from_bevy_mesh(&mesh, &ComputedColliderShape::TriMesh)
-            } else {
-                None
-            }
-        });
-        let mesh = meshes.add(mesh);
-
-        // we want to spawn a chunk even if it doesn't have a mesh, cauze it can be chunk without any blocks
-        let chunk_entity = commands
-            .spawn(ChunkMarker {
-                translation: chunk_data.translation,
-            })
-            .insert(SpatialBundle::from_transform(Transform::from_translation(
-                chunk_data.global_pos,
-            )))
-            .id();
-        let mut chunk_commands = commands.entity(chunk_entity);
-
-        if let Some(collider) = collider {
-            chunk_commands.with_children(|parent| {
-                parent.spawn((
-                    PbrBundle {
-                        mesh,
-                        // it is safe to unwrap here, since Block atlas will always contain material
-                        material: block_atlas.material.clone().unwrap(),
-                        transform: Transform::from_translation(Vec3::splat(0.0)),
-                        ..default()
-                    },
-                    RigidBody::Fixed,
-                    collider,
-                ));
-            });
+        // chunks are marked every time the player moves, but loading them takes a few frames
+        if chunk_tasks.is_spawn_pending(chunk_data.translation) {
+            continue;
         }
+        chunk_tasks
+            .waiting
+            .insert(chunk_data.translation, MeshTarget::Spawn(*chunk_data));
     }
 }
 
+fn load_priority(
+    player_query: &Query<&Transform, With<Player>>,
+    camera_query: &Query<&GlobalTransform, With<PlayerCamera>>,
+    config: &GameConfig,
+    chunk_dimensions: ChunkDimensions,
+) -> LoadPriority {
+    LoadPriority::new(
+        player_query.single().translation,
+        camera_query.single().forward(),
+        config.chunk_config.view_direction_bias,
+        chunk_dimensions,
+    )
+}
+
 pub fn unload_chunks(
     mut commands: Commands,
     mut chunk_ev: EventReader<ChunkEvent>,
-    // to_unload_chunk_query: Query<&ChunkMarker>,
+    mut game_world: ResMut<GameWorld>,
+    block_registry: Res<BlockRegistry>,
+    to_unload_chunk_query: Query<&ChunkMarker>,
+    mut chunk_entities: ResMut<ChunkEntities>,
 ) {
+    let mut unloaded = Vec::new();
     for chunk_event in chunk_ev.read() {
         let chunk_entity = match chunk_event {
             ChunkEvent::Remove(chunk_entity) => chunk_entity,
             _ => continue,
         };
-        // if let Ok(_chunk) = to_unload_chunk_query.get(*chunk_entity) {
+        if let Ok(chunk) = to_unload_chunk_query.get(*chunk_entity) {
+            chunk_entities.0.remove(&chunk.translation);
+            unloaded.push(chunk.translation);
+        }
         commands.entity(*chunk_entity).despawn_recursive();
-        // }
+    }
+    if let Err(e) = game_world.unload_chunks(&unloaded, &block_registry) {
+        error!("Could not save chunks: {}", e);
     }
 }
 
 pub fn reload_chunk(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
     mut chunk_ev: EventReader<ChunkEvent>,
-    mut game_world: ResMut<GameWorld>,
+    mut chunk_tasks: ResMut<ChunkTasks>,
     chunks_to_reload_query: Query<&ChunkMarker>,
-    blocks: Res<Assets<Block>>,
-    block_registry: Res<BlockRegistry>,
-    block_atlas: Res<Atlas<Block>>,
-    layouts: Res<Assets<TextureAtlasLayout>>,
 ) {
     for chunk_event in chunk_ev.read() {
         let chunk_entity = match chunk_event {
@@ -218,126 +165,277 @@ pub fn reload_chunk(
             _ => continue,
         };
         if let Ok(chunk_marker) = chunks_to_reload_query.get(*chunk_entity) {
-            let chunk_dimensions = game_world.chunk_dimensions;
-            let chunk = game_world.get_chunk_at(chunk_marker.translation);
-            let atlas_layout = layouts.get(&block_atlas.layout).unwrap();
-            let chunk_mesh = ChunkMesh::new(
-                chunk,
-                chunk_dimensions,
-                atlas_layout,
-                &block_registry,
-                &blocks,
-            );
+            chunk_tasks
+                .waiting
+                .insert(chunk_marker.translation, MeshTarget::Reload(*chunk_entity));
+        }
+    }
+}
 
-            let mesh = chunk_mesh.mesh();
-            // apparently Collider::from_bevy_mesh panics, because of
-            // assert!(indices.len() > 0), so I need to check it manually
-            // Why can't you rust return Err?
-            let collider = mesh.indices().and_then(|inds| {
-                if !inds.is_empty() {
-                    Collider::from_bevy_mesh(&mesh, &ComputedColliderShape::TriMesh)
-                } else {
-                    None
-                }
-            });
-            let mesh = meshes.add(mesh);
+// Light spreading through the chunk borders changes meshes of the chunks already spawned
+pub fn reload_relit_chunks(
+    mut game_world: ResMut<GameWorld>,
+    mut chunk_tasks: ResMut<ChunkTasks>,
+    chunk_lookup: ChunkLookup,
+) {
+    for translation in game_world.take_relit_chunks() {
+        if let Some(entity) = chunk_lookup.get(translation) {
+            chunk_tasks
+                .waiting
+                .insert(translation, MeshTarget::Reload(entity));
+        }
+    }
+}
 
-            if let Some(collider) = collider {
-                if let Some(mut chunk_commands) = commands.get_entity(*chunk_entity) {
-                    chunk_commands.despawn_descendants();
-                    chunk_commands.with_children(|parent| {
-                        parent.spawn((
-                            PbrBundle {
-                                mesh,
-                                // It is safe to unwrap here, since block_atlas will always contain a material
-                                material: block_atlas.material.clone().unwrap(),
-                                transform: Transform::from_translation(Vec3::splat(0.0)),
-                                ..default()
-                            },
-                            RigidBody::Fixed,
-                            collider,
-                        ));
-                    });
-                }
+// Starts loading data of the chunks waiting to be meshed and their neighbours,
+// once all of it is there starts building the mesh
+#[allow(clippy::too_many_arguments)]
+pub fn start_chunk_tasks(
+    mut chunk_tasks: ResMut<ChunkTasks>,
+    mut game_world: ResMut<GameWorld>,
+    blocks: Res<Assets<Block>>,
+    block_registry: Res<BlockRegistry>,
+    block_atlas: Res<Atlas<Block, ChunkMaterial>>,
+    layouts: Res<Assets<TextureAtlasLayout>>,
+    config: Res<GameConfig>,
+    player_query: Query<&Transform, With<Player>>,
+    camera_query: Query<&GlobalTransform, With<PlayerCamera>>,
+    mut shared_registry: Local<Option<Arc<BlockRegistry>>>,
+) {
+    let task_pool = AsyncComputeTaskPool::get();
+    let chunk_tasks = &mut *chunk_tasks;
+
+    // chunk data that finished loading
+    let mut finished = Vec::new();
+    for (translation, task) in chunk_tasks.generating.iter_mut() {
+        if let Some(chunk) = block_on(poll_once(task)) {
+            finished.push((*translation, chunk));
+        }
+    }
+    for (translation, chunk) in finished {
+        chunk_tasks.generating.remove(&translation);
+        game_world.insert_chunk(chunk, &block_registry);
+    }
+
+    // generation tasks need their own copy of the registry,
+    // it is cloned again only after the registry changes
+    if block_registry.is_changed() {
+        *shared_registry = None;
+    }
+    if chunk_tasks.waiting.is_empty() {
+        return;
+    }
+    let shared_registry = shared_registry.get_or_insert_with(|| Arc::new(block_registry.clone()));
+    let chunk_source = game_world.chunk_source();
+    let atlas_layout = layouts.get(&block_atlas.layout).unwrap();
+    let priority = load_priority(
+        &player_query,
+        &camera_query,
+        &config,
+        game_world.chunk_dimensions,
+    );
+    let max_tasks = config.chunk_config.max_chunk_tasks;
+    let mut ready_to_mesh = Vec::new();
+    for translation in priority.sorted(chunk_tasks.waiting.keys().copied()) {
+        if chunk_tasks.running() + ready_to_mesh.len() >= max_tasks {
+            break;
+        }
+        let mut all_loaded = true;
+        for needed_translation in translation.with_surrounding() {
+            if game_world.is_loaded(needed_translation) {
+                continue;
             }
+            all_loaded = false;
+            if !chunk_tasks.generating.contains_key(&needed_translation) {
+                let chunk_source = chunk_source.clone();
+                let registry = shared_registry.clone();
+                let task = task_pool
+                    .spawn(async move { chunk_source.load(needed_translation, &registry) });
+                chunk_tasks.generating.insert(needed_translation, task);
+            }
+        }
+        if all_loaded {
+            ready_to_mesh.push(translation);
         }
     }
+
+    for translation in ready_to_mesh {
+        let target = chunk_tasks.waiting.remove(&translation).unwrap();
+        let chunks = game_world
+            .get_loaded_chunk_with_neighbours(translation)
+            .unwrap();
+        let chunk = &game_world.chunk_data[&translation];
+        let chunk_mesh = ChunkMesh::new(
+            chunks,
+            game_world.chunk_dimensions,
+            atlas_layout,
+            &block_registry,
+            &blocks,
+            &config.chunk_config,
+        );
+        let chunk_collider =
+            ChunkCollider::new(chunk, game_world.chunk_dimensions, &block_registry, &blocks);
+        let task = task_pool.spawn(async move { BuiltMesh::build(chunk_mesh, chunk_collider) });
+        chunk_tasks.meshing.insert(translation, (target, task));
+    }
 }
 
-pub fn destroy_object(
+// Collects built meshes and inserts a limited amount of them into the world,
+// so a lot of chunks finishing at the same time don't cause a stall
+#[allow(clippy::too_many_arguments)]
+pub fn finish_chunk_tasks(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut chunk_tasks: ResMut<ChunkTasks>,
+    block_atlas: Res<Atlas<Block, ChunkMaterial>>,
+    config: Res<GameConfig>,
+    chunk_dimensions: Res<ChunkDimensions>,
+    player_query: Query<&Transform, With<Player>>,
+    camera_query: Query<&GlobalTransform, With<PlayerCamera>>,
+    mut chunk_entities: ResMut<ChunkEntities>,
+) {
+    let chunk_tasks = &mut *chunk_tasks;
+    let mut finished = Vec::new();
+    for (translation, (_, task)) in chunk_tasks.meshing.iter_mut() {
+        if let Some(built_mesh) = block_on(poll_once(task)) {
+            finished.push((*translation, built_mesh));
+        }
+    }
+    for (translation, built_mesh) in finished {
+        let (target, _) = chunk_tasks.meshing.remove(&translation).unwrap();
+        chunk_tasks
+            .ready
+            .push_back((translation, target, built_mesh));
+    }
+
+    let priority = load_priority(&player_query, &camera_query, &config, *chunk_dimensions);
+    chunk_tasks
+        .ready
+        .make_contiguous()
+        .sort_by(|(a, _, _), (b, _, _)| priority.of(*a).total_cmp(&priority.of(*b)));
+    let budget = config.chunk_config.chunks_inserted_per_frame;
+    for (translation, target, built_mesh) in chunk_tasks
+        .ready
+        .drain(..budget.min(chunk_tasks.ready.len()))
+    {
+        let chunk_entity = match target {
+            // we want to spawn a chunk even if it doesn't have a mesh, cauze it can be chunk without any blocks
+            MeshTarget::Spawn(chunk_data) => {
+                let chunk_entity = commands
+                    .spawn(ChunkMarker { translation })
+                    .insert(SpatialBundle::from_transform(Transform::from_translation(
+                        chunk_data.global_pos,
+                    )))
+                    .id();
+                chunk_entities.0.insert(translation, chunk_entity);
+                chunk_entity
+            }
+            MeshTarget::Reload(chunk_entity) => {
+                // chunk could have been unloaded while its mesh was built
+                let Some(mut chunk_commands) = commands.get_entity(chunk_entity) else {
+                    continue;
+                };
+                chunk_commands.despawn_descendants();
+                chunk_entity
+            }
+        };
+
+        // plants are drawn, but have nothing to collide with,
+        // so the mesh and the collider are separate children of the chunk
+        commands.entity(chunk_entity).with_children(|parent| {
+            if let Some(mesh) = built_mesh.mesh {
+                parent.spawn(MaterialMeshBundle {
+                    mesh: meshes.add(mesh),
+                    // it is safe to unwrap here, since Block atlas will always contain material
+                    material: block_atlas.material.clone().unwrap(),
+                    transform: Transform::from_translation(Vec3::splat(0.0)),
+                    ..default()
+                });
+            }
+            if let Some(collider) = built_mesh.collider {
+                parent.spawn((TransformBundle::default(), RigidBody::Fixed, collider));
+            }
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn place_block(
     buttons: Res<ButtonInput<MouseButton>>,
     looking_at: Res<LookingAt>,
-    chunks_query: Query<(Entity, &ChunkMarker)>,
+    chunk_lookup: ChunkLookup,
     mut game_world: ResMut<GameWorld>,
+    block_registry: Res<BlockRegistry>,
+    blocks: Res<Assets<Block>>,
+    rapier_context: Res<RapierContext>,
+    game_mode: Res<GameMode>,
+    mut player_query: Query<(Entity, &mut Inventory), With<Player>>,
+    camera_query: Query<&GlobalTransform, With<PlayerCamera>>,
     mut chunk_ev: EventWriter<ChunkEvent>,
 ) {
-    if buttons.just_pressed(MouseButton::Left) {
-        if let LookingAt::Something {
-            block_pos,
-            normal: _normal,
-        } = *looking_at
-        {
-            let chunk_dimensions = game_world.chunk_dimensions;
-            game_world.set_block_at(None, block_pos);
-            let mut chunk_entity = None;
-            // TODO find a better way to do this
-            for (entity, ChunkMarker { translation }) in chunks_query.iter() {
-                if *translation
-                    == ChunkTranslation::get_chunk_translation(block_pos, chunk_dimensions)
-                {
-                    chunk_entity = Some(entity);
-                }
-            }
-            if let Some(e) = chunk_entity {
-                chunk_ev.send(ChunkEvent::Reload(e));
-            }
-        }
+    if !buttons.just_pressed(MouseButton::Right) {
+        return;
+    }
+    let LookingAt::Something {
+        block_pos,
+        normal,
+        point,
+    } = *looking_at
+    else {
+        return;
+    };
+    // camera is inside of the block, there is no face to place the block against
+    if normal == Vec3::ZERO {
+        return;
+    }
+    let (player, mut inventory) = player_query.single_mut();
+    let Some(stack) = inventory.selected_stack() else {
+        return;
+    };
+    let block_id = stack.block;
+    let pos = block_pos + normal;
+    // the block can end up in the neighbouring chunk, which may not be spawned yet
+    if chunk_lookup.at_block(pos).is_none() {
+        return;
     }
-}
 
-// pub fn create_object(
-//     mut commands: Commands,
-//     mut materials: ResMut<Assets<StandardMaterial>>,
-//     mut meshes: ResMut<Assets<Mesh>>,
-//     chunk_dimension: Res<ChunkDimensions>,
-//     buttons: Res<ButtonInput<MouseButton>>,
-//     // blocks are now children of Chunk, so Transform is local
-//     looking_at_query: Query<(&GlobalTransform, &LookingAt)>,
-//     mut chunk_query: Query<(Entity, &mut Chunk, &Transform)>,
-// ) {
-//     let material_h = materials.add(Color::RED);
-//     let cube = Cuboid {
-//         half_size: Vec3::splat(0.5),
-//     };
-//     let mesh_h = meshes.add(cube.mesh());
-//     if buttons.just_pressed(MouseButton::Right) {
-//         for (transform, looking_at) in looking_at_query.iter() {
-//             // where to place new block in global coords
-//             let translation = transform.translation() + looking_at.intersection.normal;
-//             let to_chunk_translation = Chunk::get_chunk_translation(&translation, &chunk_dimension);
-//             let (chunk_entity, mut chunk, chunk_transform) = chunk_query
-//                 .iter_mut()
-//                 .filter(|(_e, chunk, _tr)| chunk.translation == to_chunk_translation)
-//                 .last()
-//                 .unwrap();
+    // some properties of the placed block depend on how it was placed
+    let placement = Placement {
+        look_direction: camera_query.single().forward(),
+        normal,
+        point,
+    };
+    let Some(block_state) = block_registry
+        .properties(block_id)
+        .and_then(|properties| block_registry.state(block_id, properties.placed_state(&placement)))
+    else {
+        return;
+    };
 
-//             let x = { translation.x - chunk_transform.translation.x };
-//             let y = { translation.y - chunk_transform.translation.y };
-//             let z = { translation.z - chunk_transform.translation.z };
+    // block can't be placed where the player stands, unless the player can go through it.
+    // Boxes are slightly smaller, so the player can place blocks right next to them
+    let collision_boxes = block_registry
+        .block_asset(block_state, &blocks)
+        .map(|(block, state)| block.collision_boxes(state))
+        .unwrap_or_default();
+    let intersects_player = collision_boxes.into_iter().any(|(min, max)| {
+        let half_size = (max - min) / 2.0 - 0.001;
+        rapier_context
+            .intersection_with_shape(
+                pos + min + half_size + 0.001,
+                Quat::IDENTITY,
+                &Collider::cuboid(half_size.x, half_size.y, half_size.z),
+                QueryFilter::new().predicate(&|entity| entity == player),
+            )
+            .is_some()
+    });
+    if intersects_player {
+        return;
+    }
 
-//             chunk.set_block_at(&Vec3::new(x, y, z), BlockId::air());
-//             commands.entity(chunk_entity).with_children(|parent| {
-//                 parent.spawn((
-//                     PbrBundle {
-//                         mesh: mesh_h.clone(),
-//                         material: material_h.clone(),
-//                         transform: Transform::from_xyz(x, y, z),
-//                         ..default()
-//                     },
-//                     RigidBody::Fixed,
-//                     Collider::cuboid(0.5, 0.5, 0.5),
-//                 ));
-//             });
-//         }
-//     }
-// }
+    if !game_mode.has_infinite_blocks() {
+        inventory.take_selected();
+    }
+    game_world.set_block_at(Some(block_state), pos, &block_registry);
+    chunk_lookup.reload_chunks_sharing_block(pos, &mut chunk_ev);
+}
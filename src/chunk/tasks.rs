@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, tasks::Task, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+
+use super::{
+    collider::ChunkCollider, mesh::ChunkMesh, Chunk, ChunkDimensions, ChunkLoadData,
+    ChunkTranslation,
+};
+
+// What to do with the chunk mesh once it is built
+#[derive(Clone, Copy, Debug)]
+pub enum MeshTarget {
+    // spawn a new chunk entity
+    Spawn(ChunkLoadData),
+    // replace the mesh of an existing chunk entity
+    Reload(Entity),
+}
+
+pub struct BuiltMesh {
+    // None if the chunk has nothing to draw
+    pub mesh: Option<Mesh>,
+    // None if the chunk has nothing to collide with
+    pub collider: Option<Collider>,
+}
+
+impl BuiltMesh {
+    // The expensive part of loading a chunk, so it's meant to be run in the background
+    pub fn build(chunk_mesh: ChunkMesh, chunk_collider: ChunkCollider) -> BuiltMesh {
+        let mesh = chunk_mesh.mesh();
+        let mesh = mesh
+            .indices()
+            .is_some_and(|indices| !indices.is_empty())
+            .then_some(mesh);
+        BuiltMesh {
+            mesh,
+            collider: chunk_collider.build(),
+        }
+    }
+}
+
+// Chunks that are being loaded or meshed in the background
+#[derive(Resource, Default)]
+pub struct ChunkTasks {
+    // chunk data being loaded from disk or generated
+    pub generating: HashMap<ChunkTranslation, Task<Chunk>>,
+    // chunks waiting for their data and data of their neighbours, to be meshed
+    pub waiting: HashMap<ChunkTranslation, MeshTarget>,
+    // newer task for the same chunk replaces the older one, which cancels it
+    pub meshing: HashMap<ChunkTranslation, (MeshTarget, Task<BuiltMesh>)>,
+    // finished meshes, only a few of them are inserted every frame
+    pub ready: VecDeque<(ChunkTranslation, MeshTarget, BuiltMesh)>,
+}
+
+// Order in which pending chunks are loaded, recalculated every frame as the player moves
+pub struct LoadPriority {
+    player_pos: Vec3,
+    view_direction: Vec3,
+    view_direction_bias: f32,
+    dimensions: ChunkDimensions,
+}
+
+impl LoadPriority {
+    pub fn new(
+        player_pos: Vec3,
+        view_direction: Vec3,
+        view_direction_bias: f32,
+        dimensions: ChunkDimensions,
+    ) -> LoadPriority {
+        LoadPriority {
+            player_pos,
+            view_direction: view_direction.normalize_or_zero(),
+            view_direction_bias,
+            dimensions,
+        }
+    }
+
+    // Smaller is loaded first: distance from the player to the center of the chunk,
+    // shortened for chunks in front of the camera and lengthened for the ones behind it
+    pub fn of(&self, chunk_translation: ChunkTranslation) -> f32 {
+        let center = Vec3::new(
+            (chunk_translation.x as f32 + 0.5) * self.dimensions.width as f32,
+            (chunk_translation.y as f32 + 0.5) * self.dimensions.height as f32,
+            (chunk_translation.z as f32 + 0.5) * self.dimensions.depth as f32,
+        );
+        let to_chunk = center - self.player_pos;
+        let facing = to_chunk.normalize_or_zero().dot(self.view_direction);
+        to_chunk.length() * (1.0 - self.view_direction_bias * facing)
+    }
+
+    // Translations sorted from the most to the least urgent
+    pub fn sorted(
+        &self,
+        translations: impl Iterator<Item = ChunkTranslation>,
+    ) -> Vec<ChunkTranslation> {
+        let mut translations: Vec<(f32, ChunkTranslation)> =
+            translations.map(|t| (self.of(t), t)).collect();
+        translations.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        translations.into_iter().map(|(_, t)| t).collect()
+    }
+}
+
+impl ChunkTasks {
+    // Number of generation and meshing tasks currently running
+    pub fn running(&self) -> usize {
+        self.generating.len() + self.meshing.len()
+    }
+
+    // Drops everything that is not needed anymore, since the player went away.
+    // Dropping a task cancels it
+    pub fn cancel_stale<F, G>(&mut self, is_visible: F, is_needed: G)
+    where
+        F: Fn(ChunkTranslation) -> bool,
+        G: Fn(ChunkTranslation) -> bool,
+    {
+        self.waiting
+            .retain(|translation, _| is_visible(*translation));
+        self.meshing
+            .retain(|translation, _| is_visible(*translation));
+        self.ready
+            .retain(|(translation, _, _)| is_visible(*translation));
+        // chunks right outside of the visible range are still needed for meshing
+        self.generating
+            .retain(|translation, _| is_needed(*translation));
+    }
+
+    // Whether chunk entity is going to be spawned for the translation
+    pub fn is_spawn_pending(&self, chunk_translation: ChunkTranslation) -> bool {
+        let is_spawn = |target: &MeshTarget| matches!(target, MeshTarget::Spawn(_));
+        self.waiting.get(&chunk_translation).is_some_and(is_spawn)
+            || self
+                .meshing
+                .get(&chunk_translation)
+                .is_some_and(|(target, _)| is_spawn(target))
+            || self.ready.iter().any(|(translation, target, _)| {
+                *translation == chunk_translation && is_spawn(target)
+            })
+    }
+}
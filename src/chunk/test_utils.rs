@@ -0,0 +1,24 @@
+use super::{storage::BlockStorage, ChunkDimensions};
+
+pub const DIMS: ChunkDimensions = ChunkDimensions {
+    width: 16,
+    height: 16,
+    depth: 16,
+};
+
+// Blocks of a DIMS sized chunk, block_at gives palette index of the block at x, y, z
+pub fn block_data<F>(block_at: F) -> BlockStorage
+where
+    F: Fn(usize, usize, usize) -> u16,
+{
+    let mut block_data = BlockStorage::new(DIMS.width * DIMS.height * DIMS.depth, 0);
+    for x in 0..DIMS.width {
+        for y in 0..DIMS.height {
+            for z in 0..DIMS.depth {
+                let index = x * DIMS.width * DIMS.height + y * DIMS.width + z;
+                block_data.set(index, block_at(x, y, z));
+            }
+        }
+    }
+    block_data
+}
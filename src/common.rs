@@ -24,11 +24,11 @@ pub enum SetupState {
 
 // Generalized resource to hold different kind of atlasses
 #[derive(Resource)]
-pub struct Atlas<T> {
+pub struct Atlas<T, M: Asset = StandardMaterial> {
     pub texture: Handle<Image>,
     pub layout: Handle<TextureAtlasLayout>,
     // TODO investigate if this is the best method for this
-    pub material: Option<Handle<StandardMaterial>>,
+    pub material: Option<Handle<M>>,
     pub phantom: PhantomData<T>,
 }
 
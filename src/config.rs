@@ -1,5 +1,9 @@
+use std::path::PathBuf;
+
 use bevy::prelude::*;
 
+use crate::{chunk::mesh::Mesher, game_mode::GameMode, inventory::HOTBAR_SIZE};
+
 #[derive(Default)]
 pub struct KeyConfig {
     pub camera_controls: CameraControls,
@@ -10,12 +14,28 @@ pub struct KeyConfig {
 
 pub struct GameControls {
     pub inventory_key: KeyCode,
+    // cycles through survival, creative and spectator
+    pub switch_game_mode: KeyCode,
+    // select hotbar slots from left to right
+    pub hotbar_keys: [KeyCode; HOTBAR_SIZE],
 }
 
 impl Default for GameControls {
     fn default() -> Self {
         GameControls {
             inventory_key: KeyCode::KeyE,
+            switch_game_mode: KeyCode::F4,
+            hotbar_keys: [
+                KeyCode::Digit1,
+                KeyCode::Digit2,
+                KeyCode::Digit3,
+                KeyCode::Digit4,
+                KeyCode::Digit5,
+                KeyCode::Digit6,
+                KeyCode::Digit7,
+                KeyCode::Digit8,
+                KeyCode::Digit9,
+            ],
         }
     }
 }
@@ -45,13 +65,167 @@ impl Default for DebugControls {
 }
 
 pub struct ChunkConfig {
-    // render_distance must be even
+    // radius of the circle of chunks loaded around the player, in chunks
     pub render_distance: usize,
+    // how many chunks are loaded above and below the player
+    pub vertical_render_distance: usize,
+    // how chunk meshes are built, greedy one produces a lot less triangles
+    pub mesher: Mesher,
+    // light is smoothed between the faces and darkened in the corners,
+    // every face has the same light all over without it
+    pub ambient_occlusion: bool,
+    // how many chunks that finished loading in the background are added to the world every frame
+    pub chunks_inserted_per_frame: usize,
+    // how many chunks can be generated or meshed in the background at the same time
+    pub max_chunk_tasks: usize,
+    // from 0 to 1, how much sooner chunks in front of the camera are loaded than the ones behind it
+    pub view_direction_bias: f32,
 }
 
 impl Default for ChunkConfig {
     fn default() -> Self {
-        ChunkConfig { render_distance: 4 }
+        ChunkConfig {
+            render_distance: 4,
+            vertical_render_distance: 2,
+            mesher: Mesher::default(),
+            ambient_occlusion: true,
+            chunks_inserted_per_frame: 8,
+            max_chunk_tasks: 32,
+            view_direction_bias: 0.5,
+        }
+    }
+}
+
+pub struct WorldGenConfig {
+    // name of the generator in GeneratorRegistry
+    pub generator: String,
+    // same seed always produces the same world
+    pub seed: u32,
+    // number of noise layers stacked on top of each other
+    pub octaves: usize,
+    // horizontal size of the biggest hills in blocks
+    pub scale: f64,
+    // how much every next octave contributes compared to the previous one
+    pub persistence: f64,
+    // how much every next octave is more detailed than the previous one
+    pub lacunarity: f64,
+    // terrain height around which hills and valleys are generated
+    pub base_height: isize,
+    // how far terrain can go above or below base_height
+    pub amplitude: f64,
+    // how many dirt blocks are between the grass and the stone
+    pub dirt_depth: isize,
+}
+
+impl Default for WorldGenConfig {
+    fn default() -> Self {
+        WorldGenConfig {
+            generator: String::from("noise"),
+            seed: 0,
+            octaves: 5,
+            scale: 96.0,
+            persistence: 0.5,
+            lacunarity: 2.0,
+            base_height: 0,
+            amplitude: 24.0,
+            dirt_depth: 3,
+        }
+    }
+}
+
+pub struct SaveConfig {
+    // directory where region files of the world are stored
+    pub world_dir: PathBuf,
+    // how often modified chunks are written to disk, in seconds
+    pub autosave_interval: f32,
+}
+
+impl Default for SaveConfig {
+    fn default() -> Self {
+        SaveConfig {
+            world_dir: PathBuf::from("saves/world"),
+            autosave_interval: 60.0,
+        }
+    }
+}
+
+pub struct PlayerConfig {
+    // movement and physics are simulated this many times per second
+    pub tick_rate: f64,
+    // in blocks per second
+    pub walk_speed: f32,
+    pub sprint_speed: f32,
+    pub sneak_speed: f32,
+    pub fly_speed: f32,
+    // how quickly the player speeds up and slows down, the higher the snappier
+    pub acceleration: f32,
+    // downward acceleration of the walking player, in blocks per second squared
+    pub gravity: f32,
+    // the fastest the player can fall, in blocks per second
+    pub terminal_velocity: f32,
+    // how high the player jumps, in blocks
+    pub jump_height: f32,
+    // highest ledge the player walks onto without jumping, in blocks,
+    // a bit more than a block, so the controller offset doesn't get in the way
+    pub step_height: f32,
+    // seconds it takes to mine a block with hardness of 1
+    pub mining_time: f32,
+}
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        PlayerConfig {
+            tick_rate: 64.0,
+            walk_speed: 4.3,
+            sprint_speed: 5.6,
+            sneak_speed: 1.3,
+            fly_speed: 10.9,
+            acceleration: 10.0,
+            gravity: 32.0,
+            terminal_velocity: 78.4,
+            jump_height: 1.25,
+            step_height: 1.05,
+            mining_time: 1.5,
+        }
+    }
+}
+
+pub struct DropConfig {
+    // how close the player has to be to pick up a dropped item, in blocks
+    pub pickup_range: f32,
+    // seconds before a dropped item can be picked up
+    pub pickup_delay: f32,
+    // seconds before a dropped item disappears
+    pub despawn_time: f32,
+}
+
+impl Default for DropConfig {
+    fn default() -> Self {
+        DropConfig {
+            pickup_range: 1.5,
+            pickup_delay: 0.5,
+            despawn_time: 300.0,
+        }
+    }
+}
+
+pub struct TimeConfig {
+    // how fast time goes in the world
+    pub ticks_per_second: f32,
+    // length of the whole day and night
+    pub ticks_per_day: u64,
+    // time new worlds start at, 0 is sunrise
+    pub start_time: u64,
+}
+
+impl Default for TimeConfig {
+    fn default() -> Self {
+        TimeConfig {
+            ticks_per_second: 20.0,
+            // 20 minutes
+            ticks_per_day: 24000,
+            start_time: 1000,
+        }
     }
 }
 
@@ -59,6 +233,13 @@ impl Default for ChunkConfig {
 pub struct GameConfig {
     pub key_config: KeyConfig,
     pub chunk_config: ChunkConfig,
+    pub world_gen_config: WorldGenConfig,
+    pub save_config: SaveConfig,
+    pub player_config: PlayerConfig,
+    pub drop_config: DropConfig,
+    pub time_config: TimeConfig,
+    // game mode of newly created worlds, saved worlds keep their own
+    pub default_game_mode: GameMode,
 }
 
 pub struct PlayerControls {
@@ -68,6 +249,7 @@ pub struct PlayerControls {
     pub strafe_right: KeyCode,
     pub jump: KeyCode,
     pub crouch: KeyCode,
+    pub sprint: KeyCode,
 }
 
 impl Default for PlayerControls {
@@ -79,6 +261,7 @@ impl Default for PlayerControls {
             strafe_right: KeyCode::KeyD,
             jump: KeyCode::Space,
             crouch: KeyCode::ShiftLeft,
+            sprint: KeyCode::ControlLeft,
         }
     }
 }
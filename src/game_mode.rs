@@ -0,0 +1,104 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::AppState, config::GameConfig, player::Player, ui::InInventory,
+    world::meta::SavedWorldMeta,
+};
+
+pub struct GameModePlugin;
+
+impl Plugin for GameModePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SavedWorldMeta>();
+        let game_mode = GameMode::load(
+            app.world.resource::<SavedWorldMeta>(),
+            app.world.resource::<GameConfig>(),
+        );
+        app.insert_resource(game_mode).add_systems(
+            Update,
+            (
+                switch_game_mode.run_if(resource_equals(InInventory::Out)),
+                apply_game_mode.run_if(resource_changed::<GameMode>),
+            )
+                .chain()
+                .run_if(in_state(AppState::Game)),
+        );
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMode {
+    // gravity, blocks are taken from the inventory and take time to mine
+    #[default]
+    Survival,
+    // flying, infinite blocks and instant breaking
+    Creative,
+    // flying through blocks without interacting with the world
+    Spectator,
+}
+
+impl GameMode {
+    // Game mode is saved with the world, new worlds start in the one from the config
+    fn load(saved_meta: &SavedWorldMeta, config: &GameConfig) -> GameMode {
+        match &saved_meta.0 {
+            Some(meta) => meta.game_mode,
+            None => config.default_game_mode,
+        }
+    }
+
+    fn next(&mut self) {
+        *self = match self {
+            Self::Survival => Self::Creative,
+            Self::Creative => Self::Spectator,
+            Self::Spectator => Self::Survival,
+        }
+    }
+
+    pub fn can_fly(&self) -> bool {
+        *self != Self::Survival
+    }
+
+    // Whether the player can break and place blocks and use the inventory
+    pub fn can_interact(&self) -> bool {
+        *self != Self::Spectator
+    }
+
+    // Placed blocks are not taken from the inventory and broken ones are not collected
+    pub fn has_infinite_blocks(&self) -> bool {
+        *self == Self::Creative
+    }
+
+    // Blocks break on click, no matter how hard they are
+    pub fn breaks_instantly(&self) -> bool {
+        *self == Self::Creative
+    }
+}
+
+fn switch_game_mode(
+    k_input: Res<ButtonInput<KeyCode>>,
+    config: Res<GameConfig>,
+    mut game_mode: ResMut<GameMode>,
+) {
+    if k_input.just_pressed(config.key_config.game_controls.switch_game_mode) {
+        game_mode.next();
+        info!("Game mode changed to {:?}", *game_mode);
+    }
+}
+
+fn apply_game_mode(
+    game_mode: Res<GameMode>,
+    mut controller_query: Query<&mut KinematicCharacterController, With<Player>>,
+) {
+    for mut controller in controller_query.iter_mut() {
+        // chunk colliders are fixed bodies, so spectator goes right through them,
+        // dropped items are dynamic ones and nobody should trip over them
+        let flags = QueryFilterFlags::EXCLUDE_SENSORS | QueryFilterFlags::EXCLUDE_DYNAMIC;
+        controller.filter_flags = match *game_mode {
+            GameMode::Spectator => flags | QueryFilterFlags::EXCLUDE_FIXED,
+            GameMode::Survival | GameMode::Creative => flags,
+        };
+    }
+}
@@ -0,0 +1,174 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+
+use super::{Inventory, ItemStack};
+use crate::{
+    block::{Block, NumericBlockId},
+    chunk::{material::ChunkMaterial, mesh::BlockItemMesh},
+    common::Atlas,
+    config::GameConfig,
+    game_mode::GameMode,
+    player::{mining::BlockBroken, Player},
+    registry::BlockRegistry,
+};
+
+// size of the dropped block compared to the placed one
+const DROPPED_ITEM_SCALE: f32 = 0.25;
+
+// Stack of blocks lying in the world, waiting to be picked up
+#[derive(Component, Debug)]
+pub struct DroppedItem {
+    pub stack: ItemStack,
+    // seconds since the item was dropped
+    pub age: f32,
+}
+
+// Mesh of the dropped item, spins and floats above the physics body
+#[derive(Component)]
+pub struct DroppedItemModel;
+
+// Meshes of dropped blocks, built once for every block that was dropped
+#[derive(Resource, Default)]
+pub struct DroppedItemMeshes(pub HashMap<NumericBlockId, Handle<Mesh>>);
+
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_block_drops(
+    mut commands: Commands,
+    mut broken_ev: EventReader<BlockBroken>,
+    game_mode: Res<GameMode>,
+    block_registry: Res<BlockRegistry>,
+    blocks: Res<Assets<Block>>,
+    block_atlas: Res<Atlas<Block, ChunkMaterial>>,
+    texture_layouts: Res<Assets<TextureAtlasLayout>>,
+    mut item_meshes: ResMut<DroppedItemMeshes>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for BlockBroken { block, block_pos } in broken_ev.read() {
+        // creative player has all the blocks already
+        if game_mode.has_infinite_blocks() {
+            continue;
+        }
+        let Some(block) = block_registry.get(*block).and_then(|h| blocks.get(h)) else {
+            continue;
+        };
+        for drop in block.drops.iter() {
+            if fastrand::f32() >= drop.probability {
+                continue;
+            }
+            let count = fastrand::u32(drop.count.clone());
+            if count == 0 {
+                continue;
+            }
+            let Some(item) = block_registry.numeric_id(&drop.item) else {
+                warn!("{:?} drops unknown block {:?}", block.id, drop.item);
+                continue;
+            };
+
+            let Some(mesh) = item_meshes.0.get(&item).cloned().or_else(|| {
+                let item_block = block_registry.get(item).and_then(|h| blocks.get(h))?;
+                let atlas = texture_layouts.get(&block_atlas.layout)?;
+                let mesh = meshes.add(
+                    // dropped blocks look like placed ones in their default state
+                    BlockItemMesh {
+                        textures: item_block
+                            .textures_of(0)
+                            .clone()
+                            .map(|v| atlas.textures[atlas.get_texture_index(v).unwrap()]),
+                        shape: item_block.model.shape(&item_block.properties, 0),
+                        atlas_size: atlas.size,
+                    }
+                    .mesh(),
+                );
+                item_meshes.0.insert(item, mesh.clone());
+                Some(mesh)
+            }) else {
+                continue;
+            };
+            spawn_dropped_item(
+                &mut commands,
+                ItemStack::new(item, count),
+                *block_pos + Vec3::splat(0.5),
+                mesh,
+                block_atlas.material.clone().unwrap_or_default(),
+            );
+        }
+    }
+}
+
+fn spawn_dropped_item(
+    commands: &mut Commands,
+    stack: ItemStack,
+    position: Vec3,
+    mesh: Handle<Mesh>,
+    material: Handle<ChunkMaterial>,
+) {
+    // items pop out of the block in a random direction
+    let pop = Vec3::new(fastrand::f32() - 0.5, 2.0, fastrand::f32() - 0.5) * 2.0;
+    let half_size = DROPPED_ITEM_SCALE / 2.0;
+    commands
+        .spawn((
+            DroppedItem { stack, age: 0.0 },
+            SpatialBundle::from_transform(Transform::from_translation(position)),
+            RigidBody::Dynamic,
+            Collider::cuboid(half_size, half_size, half_size),
+            LockedAxes::ROTATION_LOCKED,
+            Velocity::linear(pop),
+            Damping {
+                linear_damping: 1.0,
+                angular_damping: 0.0,
+            },
+            Ccd::enabled(),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                MaterialMeshBundle {
+                    mesh,
+                    material,
+                    transform: Transform::from_scale(Vec3::splat(DROPPED_ITEM_SCALE)),
+                    ..default()
+                },
+                DroppedItemModel,
+            ));
+        });
+}
+
+pub fn animate_dropped_items(
+    time: Res<Time>,
+    mut model_query: Query<&mut Transform, With<DroppedItemModel>>,
+) {
+    let elapsed = time.elapsed_seconds();
+    for mut transform in model_query.iter_mut() {
+        transform.rotation = Quat::from_rotation_y(elapsed);
+        transform.translation.y = 0.1 * (elapsed * 2.0).sin() + 0.1;
+    }
+}
+
+// Items close to the player go to the inventory, old ones disappear
+pub fn pick_up_dropped_items(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    mut item_query: Query<(Entity, &mut DroppedItem, &GlobalTransform)>,
+    mut player_query: Query<(&mut Inventory, &GlobalTransform), With<Player>>,
+) {
+    let drop_config = &config.drop_config;
+    let (mut inventory, player_tf) = player_query.single_mut();
+    for (entity, mut item, item_tf) in item_query.iter_mut() {
+        item.age += time.delta_seconds();
+        if item.age > drop_config.despawn_time {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        if item.age < drop_config.pickup_delay
+            || item_tf.translation().distance(player_tf.translation()) > drop_config.pickup_range
+        {
+            continue;
+        }
+        let left = inventory.add(item.stack.block, item.stack.count);
+        if left == 0 {
+            commands.entity(entity).despawn_recursive();
+        } else if left != item.stack.count {
+            item.stack.count = left;
+        }
+    }
+}
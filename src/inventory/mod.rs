@@ -0,0 +1,341 @@
+use std::ops::Range;
+
+use bevy::prelude::*;
+
+use self::{dropped::*, systems::*};
+use crate::{
+    block::NumericBlockId, common::AppState, player::mining::BlockBroken, ui::InInventory,
+};
+
+mod dropped;
+mod systems;
+
+// first slots of the inventory are the hotbar
+pub const HOTBAR_SIZE: usize = 9;
+pub const INVENTORY_SIZE: usize = HOTBAR_SIZE + 27;
+pub const MAX_STACK_SIZE: u32 = 64;
+
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DroppedItemMeshes>()
+            .add_systems(
+                Update,
+                (select_hotbar_slot, pick_block)
+                    .run_if(in_state(AppState::Game).and_then(resource_equals(InInventory::Out))),
+            )
+            .add_systems(
+                Update,
+                (
+                    spawn_block_drops.run_if(on_event::<BlockBroken>()),
+                    animate_dropped_items,
+                    pick_up_dropped_items,
+                )
+                    .run_if(in_state(AppState::Game)),
+            );
+    }
+}
+
+// Some amount of the same blocks in one slot
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ItemStack {
+    pub block: NumericBlockId,
+    pub count: u32,
+}
+
+impl ItemStack {
+    pub fn new(block: NumericBlockId, count: u32) -> ItemStack {
+        ItemStack { block, count }
+    }
+
+    // How many more blocks fit into this stack
+    pub fn space_left(&self) -> u32 {
+        MAX_STACK_SIZE.saturating_sub(self.count)
+    }
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct Inventory {
+    pub slots: [Option<ItemStack>; INVENTORY_SIZE],
+    // index of the selected hotbar slot
+    pub selected: usize,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Inventory {
+            slots: [None; INVENTORY_SIZE],
+            selected: 0,
+        }
+    }
+}
+
+impl Inventory {
+    pub fn selected_stack(&self) -> Option<&ItemStack> {
+        self.slots[self.selected].as_ref()
+    }
+
+    pub fn select(&mut self, slot: usize) {
+        self.selected = slot.min(HOTBAR_SIZE - 1);
+    }
+
+    // Moves selection by the amount of slots, wrapping around the hotbar
+    pub fn scroll(&mut self, amount: isize) {
+        self.selected = (self.selected as isize + amount).rem_euclid(HOTBAR_SIZE as isize) as usize;
+    }
+
+    // Adds blocks to stacks of the same block first and then to empty slots,
+    // returns how many of them didn't fit
+    pub fn add(&mut self, block: NumericBlockId, count: u32) -> u32 {
+        self.add_to(0..INVENTORY_SIZE, block, count)
+    }
+
+    // Same as add, but only uses slots in the range
+    fn add_to(&mut self, slots: Range<usize>, block: NumericBlockId, mut count: u32) -> u32 {
+        for stack in self.slots[slots.clone()].iter_mut().flatten() {
+            if count == 0 {
+                return 0;
+            }
+            if stack.block == block {
+                let added = count.min(stack.space_left());
+                stack.count += added;
+                count -= added;
+            }
+        }
+        for slot in self.slots[slots].iter_mut() {
+            if count == 0 {
+                return 0;
+            }
+            if slot.is_none() {
+                let added = count.min(MAX_STACK_SIZE);
+                *slot = Some(ItemStack::new(block, added));
+                count -= added;
+            }
+        }
+        count
+    }
+
+    // Left click on the slot while holding a stack on the cursor(or nothing):
+    // merges held stack into the slot if they are the same block,
+    // otherwise swaps them, which also picks up or puts down the whole stack
+    pub fn click_slot(&mut self, slot: usize, held: &mut Option<ItemStack>) {
+        if let (Some(stack), Some(held_stack)) = (self.slots[slot].as_mut(), held.as_mut()) {
+            if stack.block == held_stack.block {
+                let moved = held_stack.count.min(stack.space_left());
+                stack.count += moved;
+                held_stack.count -= moved;
+                if held_stack.count == 0 {
+                    *held = None;
+                }
+                return;
+            }
+        }
+        std::mem::swap(&mut self.slots[slot], held);
+    }
+
+    // Right click on the slot: with empty cursor picks up half of the stack,
+    // otherwise puts one block of the held stack into the slot
+    pub fn split_slot(&mut self, slot: usize, held: &mut Option<ItemStack>) {
+        match held {
+            None => {
+                let Some(stack) = self.slots[slot].as_mut() else {
+                    return;
+                };
+                let taken = stack.count.div_ceil(2);
+                stack.count -= taken;
+                *held = Some(ItemStack::new(stack.block, taken));
+                if stack.count == 0 {
+                    self.slots[slot] = None;
+                }
+            }
+            Some(held_stack) => {
+                match self.slots[slot].as_mut() {
+                    None => self.slots[slot] = Some(ItemStack::new(held_stack.block, 1)),
+                    Some(stack) if stack.block == held_stack.block && stack.space_left() > 0 => {
+                        stack.count += 1
+                    }
+                    Some(_) => return,
+                }
+                held_stack.count -= 1;
+                if held_stack.count == 0 {
+                    *held = None;
+                }
+            }
+        }
+    }
+
+    // Shift click: moves the stack from the hotbar to the rest of the inventory or back
+    pub fn quick_move(&mut self, slot: usize) {
+        let Some(stack) = self.slots[slot].take() else {
+            return;
+        };
+        let target = if slot < HOTBAR_SIZE {
+            HOTBAR_SIZE..INVENTORY_SIZE
+        } else {
+            0..HOTBAR_SIZE
+        };
+        let left = self.add_to(target, stack.block, stack.count);
+        if left > 0 {
+            self.slots[slot] = Some(ItemStack::new(stack.block, left));
+        }
+    }
+
+    // Selects the hotbar slot with the block, if there is none
+    // the selected slot is replaced with a full stack of it
+    pub fn pick(&mut self, block: NumericBlockId) {
+        let in_hotbar = self.slots[0..HOTBAR_SIZE]
+            .iter()
+            .position(|slot| slot.is_some_and(|stack| stack.block == block));
+        match in_hotbar {
+            Some(slot) => self.select(slot),
+            None => self.slots[self.selected] = Some(ItemStack::new(block, MAX_STACK_SIZE)),
+        }
+    }
+
+    // Removes one block from the selected slot, returning what it was
+    pub fn take_selected(&mut self) -> Option<NumericBlockId> {
+        let slot = &mut self.slots[self.selected];
+        let stack = slot.as_mut()?;
+        let block = stack.block;
+        stack.count -= 1;
+        if stack.count == 0 {
+            *slot = None;
+        }
+        Some(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE: NumericBlockId = NumericBlockId(1);
+    const DIRT: NumericBlockId = NumericBlockId(2);
+
+    #[test]
+    fn added_blocks_go_to_matching_stacks_first() {
+        let mut inventory = Inventory::default();
+        inventory.slots[3] = Some(ItemStack::new(DIRT, 10));
+        inventory.slots[5] = Some(ItemStack::new(STONE, 10));
+        assert_eq!(inventory.add(STONE, 20), 0);
+        assert_eq!(inventory.slots[0], None);
+        assert_eq!(inventory.slots[3], Some(ItemStack::new(DIRT, 10)));
+        assert_eq!(inventory.slots[5], Some(ItemStack::new(STONE, 30)));
+    }
+
+    #[test]
+    fn full_stacks_overflow_into_empty_slots() {
+        let mut inventory = Inventory::default();
+        inventory.slots[2] = Some(ItemStack::new(STONE, MAX_STACK_SIZE - 4));
+        assert_eq!(inventory.add(STONE, 4 + MAX_STACK_SIZE + 6), 0);
+        assert_eq!(
+            inventory.slots[2],
+            Some(ItemStack::new(STONE, MAX_STACK_SIZE))
+        );
+        // empty slots are filled in order
+        assert_eq!(
+            inventory.slots[0],
+            Some(ItemStack::new(STONE, MAX_STACK_SIZE))
+        );
+        assert_eq!(inventory.slots[1], Some(ItemStack::new(STONE, 6)));
+    }
+
+    #[test]
+    fn blocks_that_dont_fit_are_returned() {
+        let mut inventory = Inventory {
+            slots: [Some(ItemStack::new(DIRT, 1)); INVENTORY_SIZE],
+            ..Default::default()
+        };
+        inventory.slots[7] = Some(ItemStack::new(STONE, MAX_STACK_SIZE - 5));
+        assert_eq!(inventory.add(STONE, 12), 7);
+        assert_eq!(
+            inventory.slots[7],
+            Some(ItemStack::new(STONE, MAX_STACK_SIZE))
+        );
+        assert_eq!(inventory.add(STONE, 3), 3);
+    }
+
+    #[test]
+    fn click_swaps_different_blocks_and_merges_same_ones() {
+        let mut inventory = Inventory::default();
+        inventory.slots[0] = Some(ItemStack::new(STONE, 5));
+        let mut held = Some(ItemStack::new(DIRT, 3));
+        inventory.click_slot(0, &mut held);
+        assert_eq!(inventory.slots[0], Some(ItemStack::new(DIRT, 3)));
+        assert_eq!(held, Some(ItemStack::new(STONE, 5)));
+
+        // picking up and putting down are swaps with an empty slot or cursor
+        inventory.click_slot(1, &mut held);
+        assert_eq!(inventory.slots[1], Some(ItemStack::new(STONE, 5)));
+        assert_eq!(held, None);
+
+        inventory.slots[2] = Some(ItemStack::new(STONE, MAX_STACK_SIZE - 2));
+        let mut held = Some(ItemStack::new(STONE, 7));
+        inventory.click_slot(2, &mut held);
+        assert_eq!(
+            inventory.slots[2],
+            Some(ItemStack::new(STONE, MAX_STACK_SIZE))
+        );
+        assert_eq!(held, Some(ItemStack::new(STONE, 5)));
+        inventory.click_slot(1, &mut held);
+        assert_eq!(inventory.slots[1], Some(ItemStack::new(STONE, 10)));
+        assert_eq!(held, None);
+    }
+
+    #[test]
+    fn split_takes_bigger_half_and_puts_down_one_block() {
+        let mut inventory = Inventory::default();
+        inventory.slots[0] = Some(ItemStack::new(STONE, 7));
+        let mut held = None;
+        inventory.split_slot(0, &mut held);
+        assert_eq!(inventory.slots[0], Some(ItemStack::new(STONE, 3)));
+        assert_eq!(held, Some(ItemStack::new(STONE, 4)));
+
+        inventory.split_slot(0, &mut held);
+        inventory.split_slot(1, &mut held);
+        assert_eq!(inventory.slots[0], Some(ItemStack::new(STONE, 4)));
+        assert_eq!(inventory.slots[1], Some(ItemStack::new(STONE, 1)));
+        assert_eq!(held, Some(ItemStack::new(STONE, 2)));
+
+        // nothing is put down onto a different block
+        inventory.slots[2] = Some(ItemStack::new(DIRT, 1));
+        inventory.split_slot(2, &mut held);
+        assert_eq!(inventory.slots[2], Some(ItemStack::new(DIRT, 1)));
+        assert_eq!(held, Some(ItemStack::new(STONE, 2)));
+
+        // a single block is picked up whole
+        let mut held = None;
+        inventory.split_slot(1, &mut held);
+        assert_eq!(inventory.slots[1], None);
+        assert_eq!(held, Some(ItemStack::new(STONE, 1)));
+    }
+
+    #[test]
+    fn quick_move_goes_between_hotbar_and_the_rest() {
+        let mut inventory = Inventory::default();
+        inventory.slots[4] = Some(ItemStack::new(STONE, 10));
+        inventory.slots[HOTBAR_SIZE + 5] = Some(ItemStack::new(STONE, MAX_STACK_SIZE - 3));
+        inventory.quick_move(4);
+        assert_eq!(inventory.slots[4], None);
+        assert_eq!(
+            inventory.slots[HOTBAR_SIZE + 5],
+            Some(ItemStack::new(STONE, MAX_STACK_SIZE))
+        );
+        assert_eq!(inventory.slots[HOTBAR_SIZE], Some(ItemStack::new(STONE, 7)));
+
+        inventory.quick_move(HOTBAR_SIZE);
+        assert_eq!(inventory.slots[HOTBAR_SIZE], None);
+        assert_eq!(inventory.slots[0], Some(ItemStack::new(STONE, 7)));
+
+        // whatever doesn't fit stays in the slot
+        for slot in 0..HOTBAR_SIZE {
+            inventory.slots[slot] = Some(ItemStack::new(DIRT, 1));
+        }
+        inventory.quick_move(HOTBAR_SIZE + 5);
+        assert_eq!(
+            inventory.slots[HOTBAR_SIZE + 5],
+            Some(ItemStack::new(STONE, MAX_STACK_SIZE))
+        );
+    }
+}
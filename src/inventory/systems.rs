@@ -0,0 +1,55 @@
+use bevy::{input::mouse::MouseWheel, prelude::*};
+
+use super::Inventory;
+use crate::{
+    config::GameConfig,
+    game_mode::GameMode,
+    player::{LookingAt, Player},
+    registry::BlockRegistry,
+    world::GameWorld,
+};
+
+pub fn select_hotbar_slot(
+    k_input: Res<ButtonInput<KeyCode>>,
+    mut wheel_ev: EventReader<MouseWheel>,
+    config: Res<GameConfig>,
+    mut inventory_query: Query<&mut Inventory, With<Player>>,
+) {
+    let mut inventory = inventory_query.single_mut();
+    let hotbar_keys = &config.key_config.game_controls.hotbar_keys;
+    if let Some(slot) = hotbar_keys
+        .iter()
+        .position(|key| k_input.just_pressed(*key))
+    {
+        inventory.select(slot);
+    }
+
+    let scroll: f32 = wheel_ev.read().map(|ev| ev.y).sum();
+    // scrolling down goes to the next slot
+    if scroll < 0.0 {
+        inventory.scroll(1);
+    } else if scroll > 0.0 {
+        inventory.scroll(-1);
+    }
+}
+
+// Middle click in creative puts the block the player is looking at into the hotbar
+pub fn pick_block(
+    buttons: Res<ButtonInput<MouseButton>>,
+    looking_at: Res<LookingAt>,
+    game_mode: Res<GameMode>,
+    mut game_world: ResMut<GameWorld>,
+    block_registry: Res<BlockRegistry>,
+    mut inventory_query: Query<&mut Inventory, With<Player>>,
+) {
+    if !buttons.just_pressed(MouseButton::Middle) || !game_mode.has_infinite_blocks() {
+        return;
+    }
+    let LookingAt::Something { block_pos, .. } = *looking_at else {
+        return;
+    };
+    let block_state = game_world.get_block_at(block_pos, &block_registry);
+    if let Some((block_id, _)) = block_state.and_then(|state| block_registry.block_of(state)) {
+        inventory_query.single_mut().pick(block_id);
+    }
+}
@@ -5,7 +5,9 @@ use block::BlockPlugin;
 use camera::CameraPlugin;
 use chunk::ChunkPlugin;
 use common::AppState;
-use config::ConfigPlugin;
+use config::{ConfigPlugin, GameConfig};
+use game_mode::GameModePlugin;
+use inventory::InventoryPlugin;
 use player::PlayerPlugin;
 use ui::UiPlugin;
 use world::GameWorldPlugin;
@@ -15,12 +17,17 @@ mod camera;
 mod chunk;
 mod common;
 mod config;
+mod game_mode;
+mod inventory;
 mod player;
 mod registry;
 mod ui;
 mod world;
 
 fn main() {
+    // physics runs at the same fixed rate as the player movement,
+    // rapier checks the timestep mode when its plugin is added, so it's set before that
+    let tick_rate = GameConfig::default().player_config.tick_rate;
     App::new()
         .init_state::<AppState>()
         .insert_resource(ClearColor(Color::rgb(0.1, 0.0, 0.15)))
@@ -43,7 +50,14 @@ fn main() {
         //     global: true,
         //     default_color: Color::WHITE,
         // })
-        .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+        .insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed {
+                dt: 1.0 / tick_rate as f32,
+                substeps: 1,
+            },
+            ..default()
+        })
+        .add_plugins(RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule())
         // here is the gravity in rapier, I searched to long on the internet, so I will save it here for future reference
         // .insert_resource(RapierConfiguration {
         //     gravity: Vec3::ZERO,
@@ -57,6 +71,8 @@ fn main() {
             ChunkPlugin,
             PlayerPlugin,
             GameWorldPlugin,
+            InventoryPlugin,
+            GameModePlugin,
         ))
         .run();
 }
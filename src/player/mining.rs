@@ -0,0 +1,171 @@
+use bevy::{
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::texture::{ImageLoaderSettings, ImageSampler},
+};
+
+use super::LookingAt;
+use crate::{
+    block::{Block, NumericBlockId},
+    chunk::{ChunkEvent, ChunkLookup},
+    config::GameConfig,
+    game_mode::GameMode,
+    registry::BlockRegistry,
+    world::GameWorld,
+};
+
+// how many crack textures are drawn over the block while it's being mined
+const CRACK_STAGES: usize = 10;
+
+// Block the player is mining and how far along it is, from 0 to 1
+#[derive(Resource, Debug, Default, PartialEq)]
+pub struct Mining {
+    pub target: Option<Vec3>,
+    pub progress: f32,
+}
+
+// Materials with crack textures from the least to the most broken
+#[derive(Resource)]
+pub struct CrackMaterials(pub Vec<Handle<StandardMaterial>>);
+
+// Sent when the player breaks a block
+#[derive(Event, Debug)]
+pub struct BlockBroken {
+    pub block: NumericBlockId,
+    // global position of the block corner with the lowest coordinates
+    pub block_pos: Vec3,
+}
+
+// Cube drawn over the block being mined
+#[derive(Component)]
+pub struct CrackOverlay;
+
+// Holding the left button mines the block the player is looking at,
+// looking at another block starts over
+#[allow(clippy::too_many_arguments)]
+pub fn mine_block(
+    time: Res<Time>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    looking_at: Res<LookingAt>,
+    config: Res<GameConfig>,
+    game_mode: Res<GameMode>,
+    chunk_lookup: ChunkLookup,
+    mut game_world: ResMut<GameWorld>,
+    block_registry: Res<BlockRegistry>,
+    blocks: Res<Assets<Block>>,
+    mut mining: ResMut<Mining>,
+    mut chunk_ev: EventWriter<ChunkEvent>,
+    mut broken_ev: EventWriter<BlockBroken>,
+) {
+    let LookingAt::Something { block_pos, .. } = *looking_at else {
+        mining.set_if_neq(Mining::default());
+        return;
+    };
+    // only spawned chunks can be changed, the rest aren't even visible yet
+    if !buttons.pressed(MouseButton::Left) || chunk_lookup.at_block(block_pos).is_none() {
+        mining.set_if_neq(Mining::default());
+        return;
+    }
+
+    if game_mode.breaks_instantly() {
+        if !buttons.just_pressed(MouseButton::Left) {
+            return;
+        }
+    } else {
+        if mining.target != Some(block_pos) {
+            *mining = Mining {
+                target: Some(block_pos),
+                progress: 0.0,
+            };
+        }
+        let Some(block_state) = game_world.get_block_at(block_pos, &block_registry) else {
+            return;
+        };
+        let hardness = block_registry
+            .block_asset(block_state, &blocks)
+            .map_or(0.0, |(block, _)| block.hardness);
+        let mining_time = hardness * config.player_config.mining_time;
+        mining.progress = if mining_time > 0.0 {
+            mining.progress + time.delta_seconds() / mining_time
+        } else {
+            1.0
+        };
+        if mining.progress < 1.0 {
+            return;
+        }
+        *mining = Mining::default();
+    }
+
+    let broken = game_world.set_block_at(None, block_pos, &block_registry);
+    if let Some((block, _)) = broken.and_then(|state| block_registry.block_of(state)) {
+        broken_ev.send(BlockBroken { block, block_pos });
+    }
+    chunk_lookup.reload_chunks_sharing_block(block_pos, &mut chunk_ev);
+}
+
+// Mining doesn't go on while the inventory is open, the block has to be mined from the start
+pub fn stop_mining(mut mining: ResMut<Mining>) {
+    mining.set_if_neq(Mining::default());
+}
+
+pub fn spawn_crack_overlay(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let crack_materials: Vec<Handle<StandardMaterial>> = (0..CRACK_STAGES)
+        .map(|stage| {
+            let texture = asset_server.load_with_settings(
+                format!("textures/cracks/crack_{}.png", stage),
+                |settings: &mut ImageLoaderSettings| settings.sampler = ImageSampler::nearest(),
+            );
+            // white parts of the texture leave the block as is, darker ones darken it
+            materials.add(StandardMaterial {
+                base_color_texture: Some(texture),
+                alpha_mode: AlphaMode::Multiply,
+                unlit: true,
+                ..default()
+            })
+        })
+        .collect();
+
+    // slightly bigger than the block, so it's drawn on top of it
+    let mesh = meshes.add(Cuboid {
+        half_size: Vec3::splat(0.502),
+    });
+    commands.spawn((
+        PbrBundle {
+            mesh,
+            material: crack_materials[0].clone(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        NotShadowCaster,
+        CrackOverlay,
+    ));
+    commands.insert_resource(CrackMaterials(crack_materials));
+}
+
+pub fn update_crack_overlay(
+    mining: Res<Mining>,
+    crack_materials: Res<CrackMaterials>,
+    mut overlay_query: Query<
+        (
+            &mut Transform,
+            &mut Visibility,
+            &mut Handle<StandardMaterial>,
+        ),
+        With<CrackOverlay>,
+    >,
+) {
+    let (mut transform, mut visibility, mut material) = overlay_query.single_mut();
+    let Some(block_pos) = mining.target.filter(|_| mining.progress > 0.0) else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    let stage = ((mining.progress * CRACK_STAGES as f32) as usize).min(CRACK_STAGES - 1);
+    *visibility = Visibility::Inherited;
+    transform.translation = block_pos + Vec3::splat(0.5);
+    *material = crack_materials.0[stage].clone();
+}
@@ -1,28 +1,61 @@
 use bevy::{input::mouse::MouseMotion, prelude::*, window::PrimaryWindow};
 use bevy_rapier3d::{control::KinematicCharacterController, prelude::*};
 
+use self::{mining::*, movement::*};
 use crate::{
     camera::{CameraPerspective, PlayerCamera},
+    chunk::ChunkSystems,
     common::AppState,
     config::GameConfig,
+    game_mode::GameMode,
+    inventory::Inventory,
+    registry::BlockRegistry,
+    ui::InInventory,
     world::GameWorld,
 };
 
+pub mod mining;
+mod movement;
+
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
+        let tick_rate = app.world.resource::<GameConfig>().player_config.tick_rate;
         app.init_resource::<LookingAt>()
-            .add_systems(OnEnter(AppState::Game), spawn_player)
+            .insert_resource(Time::<Fixed>::from_hz(tick_rate))
+            .init_resource::<Mining>()
+            .add_event::<BlockBroken>()
+            .add_systems(OnEnter(AppState::Game), (spawn_player, spawn_crack_overlay))
+            .add_systems(
+                FixedUpdate,
+                move_player
+                    .before(PhysicsSet::SyncBackend)
+                    .run_if(in_state(AppState::Game)),
+            )
             .add_systems(
                 Update,
                 (
-                    move_player,
-                    rotate_player_and_camera,
+                    read_movement_input,
+                    // gameplay input is ignored while the inventory is open
+                    rotate_player_and_camera.run_if(resource_equals(InInventory::Out)),
                     block_selection,
                     draw_block_selection,
+                    update_crack_overlay
+                        .after(ChunkSystems::PlayerInput)
+                        .run_if(resource_changed::<Mining>),
                 )
                     .run_if(in_state(AppState::Game)),
+            )
+            .add_systems(
+                Update,
+                (
+                    mine_block
+                        .after(block_selection)
+                        .run_if(resource_equals(InInventory::Out)),
+                    stop_mining.run_if(resource_equals(InInventory::In)),
+                )
+                    .in_set(ChunkSystems::PlayerInput),
             );
     }
 }
@@ -31,8 +64,8 @@ impl Plugin for PlayerPlugin {
 pub struct Player {
     pub camera_yaw: f32,
     pub camera_pitch: f32,
+    // in blocks per second
     pub velocity: Vec3,
-    pub friction: f32,
 }
 
 impl Default for Player {
@@ -41,7 +74,6 @@ impl Default for Player {
             camera_yaw: 0.0,
             camera_pitch: 0.0,
             velocity: Vec3::ZERO,
-            friction: 0.8,
         }
     }
 }
@@ -54,7 +86,10 @@ pub enum LookingAt {
     Something {
         // Position of block in global coords
         block_pos: Vec3,
+        // Normal of the block face camera is looking at
         normal: Vec3,
+        // Point on that face camera is looking at
+        point: Vec3,
     },
 }
 
@@ -62,7 +97,15 @@ fn spawn_player(
     mut commands: Commands,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
+    game_world: Res<GameWorld>,
+    config: Res<GameConfig>,
 ) {
+    // spawn the player right above the ground
+    let spawn_height = game_world
+        .generator
+        .surface_height(0, 0, game_world.seed)
+        .unwrap_or(0) as f32
+        + 2.0;
     let player = Cuboid {
         half_size: Vec3::new(0.3, 0.75, 0.3),
     };
@@ -71,15 +114,24 @@ fn spawn_player(
 
     commands
         .spawn(Player::default())
+        .insert(Inventory::default())
+        .insert(MovementInput::default())
         .insert(PbrBundle {
             mesh,
             material,
-            transform: Transform::from_xyz(0.0, 1.0, 0.0),
+            transform: Transform::from_xyz(0.5, spawn_height, 0.5),
             ..default()
         })
         .insert(RigidBody::KinematicPositionBased)
         .insert(Collider::cuboid(0.3, 0.75, 0.3))
-        .insert(KinematicCharacterController::default())
+        .insert(KinematicCharacterController {
+            autostep: Some(CharacterAutostep {
+                max_height: CharacterLength::Absolute(config.player_config.step_height),
+                min_width: CharacterLength::Absolute(0.3),
+                include_dynamic_bodies: false,
+            }),
+            ..default()
+        })
         .with_children(|parent| {
             parent.spawn((
                 Camera3dBundle {
@@ -126,82 +178,19 @@ fn rotate_player_and_camera(
     player_transform.rotation = Quat::from_axis_angle(Vec3::Y, yaw_radians);
 }
 
-fn move_player(
-    time: Res<Time>,
-    k_input: Res<ButtonInput<KeyCode>>,
-    config: Res<GameConfig>,
-    mut player_query: Query<(&mut KinematicCharacterController, &mut Player, &Transform)>,
-) {
-    let k_config = &config.key_config;
-    let (mut player_controller, mut player_options, player_tf) = player_query.single_mut();
-    let (axis_x, axis_y, axis_z) = (
-        axis_movement(
-            &k_input,
-            k_config.player_controls.strafe_right,
-            k_config.player_controls.strafe_left,
-        ),
-        axis_movement(
-            &k_input,
-            k_config.player_controls.jump,
-            k_config.player_controls.crouch,
-        ),
-        axis_movement(
-            &k_input,
-            k_config.player_controls.move_back,
-            k_config.player_controls.move_forward,
-        ),
-    );
-
-    let rotation = player_tf.rotation;
-    let forward_vector = rotation.mul_vec3(Vec3::Z).normalize();
-    let forward_walk_vector = Vec3::new(forward_vector.x, 0.0, forward_vector.z).normalize();
-    let strafe_vector = Quat::from_rotation_y(90.0f32.to_radians())
-        .mul_vec3(forward_walk_vector)
-        .normalize();
-
-    let accel = strafe_vector * axis_x + forward_walk_vector * axis_z + Vec3::Y * axis_y;
-    let accel = if accel.length() != 0.0 {
-        accel.normalize()
-    } else {
-        Vec3::ZERO
-    };
-
-    let friction = if player_options.velocity.length() != 0.0 {
-        player_options.velocity.normalize() * -1.0 * player_options.friction
-    } else {
-        Vec3::ZERO
-    };
-
-    player_options.velocity += accel * time.delta_seconds();
-    let delta_friction = friction * time.delta_seconds();
-    player_options.velocity = if (player_options.velocity + delta_friction).signum()
-        != player_options.velocity.signum()
-    {
-        Vec3::ZERO
-    } else {
-        player_options.velocity + delta_friction
-    };
-    player_controller.translation = Some(player_options.velocity);
-}
-
-fn axis_movement(input: &Res<ButtonInput<KeyCode>>, plus: KeyCode, minus: KeyCode) -> f32 {
-    let mut axis = 0.0;
-    if input.pressed(plus) {
-        axis += 1.0;
-    }
-    if input.pressed(minus) {
-        axis -= 1.0;
-    }
-    axis
-}
-
 fn block_selection(
     mut game_world: ResMut<GameWorld>,
+    block_registry: Res<BlockRegistry>,
     mut looking_at: ResMut<LookingAt>,
+    game_mode: Res<GameMode>,
     camera_q: Query<(&Camera, &GlobalTransform), With<PlayerCamera>>,
     window_q: Query<&Window, With<PrimaryWindow>>,
 ) {
     *looking_at = LookingAt::Nothing;
+    // nothing to select, if the player can't interact with blocks
+    if !game_mode.can_interact() {
+        return;
+    }
 
     let (camera, camera_transform) = camera_q.single();
     let Some(cursor_position) = window_q.single().cursor_position() else {
@@ -210,20 +199,15 @@ fn block_selection(
     let Some(ray) = camera.viewport_to_world(camera_transform, cursor_position) else {
         return;
     };
-    let max_toi = 8;
-    let toi_step = 10;
-
-    for i in 1..(max_toi * toi_step) {
-        if game_world
-            .get_block_at((ray.get_point(i as f32 / toi_step as f32)).floor())
-            .is_some()
-        {
-            let block_pos = (ray.get_point(i as f32 / toi_step as f32)).floor();
-            *looking_at = LookingAt::Something {
-                block_pos,
-                normal: Vec3::ZERO,
-            };
-            return;
+    // how far the player can reach, in blocks
+    let max_distance = 8.0;
+
+    if let Some(hit) = game_world.raycast(ray.origin, *ray.direction, max_distance, &block_registry)
+    {
+        *looking_at = LookingAt::Something {
+            block_pos: hit.block_pos,
+            normal: hit.normal,
+            point: ray.origin + *ray.direction * hit.distance,
         };
     }
 }
@@ -231,10 +215,7 @@ fn block_selection(
 fn draw_block_selection(mut gizmos: Gizmos, looking_at: Res<LookingAt>) {
     match *looking_at {
         LookingAt::Nothing => (),
-        LookingAt::Something {
-            block_pos,
-            normal: _,
-        } => gizmos.primitive_3d(
+        LookingAt::Something { block_pos, .. } => gizmos.primitive_3d(
             Cuboid {
                 half_size: Vec3::splat(0.5),
             },
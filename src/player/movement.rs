@@ -0,0 +1,314 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::Player;
+use crate::{
+    config::{GameConfig, PlayerConfig},
+    game_mode::GameMode,
+    ui::InInventory,
+};
+
+// Keys held by the player, read every frame and used by the fixed timestep movement
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct MovementInput {
+    // direction the player wants to go in world space,
+    // vertical part is only used while flying
+    pub direction: Vec3,
+    pub jump: bool,
+    pub sneak: bool,
+    pub sprint: bool,
+}
+
+impl Player {
+    // Advances the velocity by dt seconds and returns how far the player moves during them.
+    // Both are integrated exactly, so the same input gives the same result at any tick rate
+    pub fn simulate(
+        &mut self,
+        input: &MovementInput,
+        config: &PlayerConfig,
+        flying: bool,
+        grounded: bool,
+        dt: f32,
+    ) -> Vec3 {
+        if flying {
+            let target = input.direction.normalize_or_zero() * config.fly_speed;
+            let (velocity, displacement) = approach(self.velocity, target, config.acceleration, dt);
+            self.velocity = velocity;
+            return displacement;
+        }
+
+        let speed = if input.sneak {
+            config.sneak_speed
+        } else if input.sprint {
+            config.sprint_speed
+        } else {
+            config.walk_speed
+        };
+        let direction = Vec3::new(input.direction.x, 0.0, input.direction.z);
+        let target = direction.normalize_or_zero() * speed;
+        let horizontal = Vec3::new(self.velocity.x, 0.0, self.velocity.z);
+        let (horizontal, mut displacement) = approach(horizontal, target, config.acceleration, dt);
+
+        let mut fall_speed = self.velocity.y;
+        if grounded && input.jump {
+            fall_speed = (2.0 * config.gravity * config.jump_height).sqrt();
+        }
+        let (fall_speed, fall) = fall(fall_speed, config, dt);
+        displacement.y = fall;
+        self.velocity = horizontal + Vec3::Y * fall_speed;
+        displacement
+    }
+}
+
+// Velocity that exponentially approaches the target and the distance travelled with it
+fn approach(velocity: Vec3, target: Vec3, acceleration: f32, dt: f32) -> (Vec3, Vec3) {
+    let decay = (-acceleration * dt).exp();
+    let displacement = target * dt + (velocity - target) * (1.0 - decay) / acceleration;
+    (target + (velocity - target) * decay, displacement)
+}
+
+// Vertical speed accelerated by gravity up to the terminal velocity
+// and the vertical distance travelled with it
+fn fall(speed: f32, config: &PlayerConfig, dt: f32) -> (f32, f32) {
+    let speed = speed.max(-config.terminal_velocity);
+    // time until the terminal velocity is reached
+    let accelerating = ((speed + config.terminal_velocity) / config.gravity).min(dt);
+    let distance = speed * accelerating
+        - 0.5 * config.gravity * accelerating * accelerating
+        - config.terminal_velocity * (dt - accelerating);
+    let speed = (speed - config.gravity * dt).max(-config.terminal_velocity);
+    (speed, distance)
+}
+
+pub fn read_movement_input(
+    k_input: Res<ButtonInput<KeyCode>>,
+    config: Res<GameConfig>,
+    in_inventory: Res<InInventory>,
+    mut player_query: Query<(&mut MovementInput, &Transform), With<Player>>,
+) {
+    let (mut input, player_tf) = player_query.single_mut();
+    // player keeps falling with the inventory open, but doesn't walk
+    if *in_inventory == InInventory::In {
+        *input = MovementInput::default();
+        return;
+    }
+
+    let controls = &config.key_config.player_controls;
+    let (axis_x, axis_y, axis_z) = (
+        axis_movement(&k_input, controls.strafe_right, controls.strafe_left),
+        axis_movement(&k_input, controls.jump, controls.crouch),
+        axis_movement(&k_input, controls.move_back, controls.move_forward),
+    );
+
+    let forward_vector = player_tf.rotation.mul_vec3(Vec3::Z).normalize();
+    let forward_walk_vector = Vec3::new(forward_vector.x, 0.0, forward_vector.z).normalize();
+    let strafe_vector = Quat::from_rotation_y(90.0f32.to_radians())
+        .mul_vec3(forward_walk_vector)
+        .normalize();
+
+    *input = MovementInput {
+        direction: strafe_vector * axis_x + forward_walk_vector * axis_z + Vec3::Y * axis_y,
+        jump: k_input.pressed(controls.jump),
+        sneak: k_input.pressed(controls.crouch),
+        sprint: k_input.pressed(controls.sprint),
+    };
+}
+
+fn axis_movement(input: &ButtonInput<KeyCode>, plus: KeyCode, minus: KeyCode) -> f32 {
+    let mut axis = 0.0;
+    if input.pressed(plus) {
+        axis += 1.0;
+    }
+    if input.pressed(minus) {
+        axis -= 1.0;
+    }
+    axis
+}
+
+type PlayerMovement<'a> = (
+    Entity,
+    &'a mut KinematicCharacterController,
+    Option<&'a KinematicCharacterControllerOutput>,
+    &'a mut Player,
+    &'a MovementInput,
+    &'a Transform,
+    &'a Collider,
+);
+
+pub fn move_player(
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    game_mode: Res<GameMode>,
+    rapier_context: Res<RapierContext>,
+    mut player_query: Query<PlayerMovement>,
+) {
+    let (
+        player,
+        mut player_controller,
+        controller_output,
+        mut player_options,
+        input,
+        player_tf,
+        collider,
+    ) = player_query.single_mut();
+    let flying = game_mode.can_fly();
+    let grounded = controller_output.is_some_and(|output| output.grounded);
+
+    if !flying {
+        if grounded {
+            player_options.velocity.y = player_options.velocity.y.max(0.0);
+        } else if let Some(output) = controller_output {
+            // hit the ceiling, jump is over
+            if output.desired_translation.y > 0.0
+                && output.effective_translation.y < output.desired_translation.y * 0.5
+            {
+                player_options.velocity.y = 0.0;
+            }
+        }
+    }
+
+    let mut displacement = player_options.simulate(
+        input,
+        &config.player_config,
+        flying,
+        grounded,
+        time.delta_seconds(),
+    );
+
+    // sneaking player stops at the edge instead of falling off it,
+    // every axis is checked on its own, so the player can still slide along the edge
+    if !flying && grounded && input.sneak {
+        let mut position = player_tf.translation;
+        for axis in [Vec3::X, Vec3::Z] {
+            let step = displacement * axis;
+            let next_position =
+                Transform::from_translation(position + step).with_rotation(player_tf.rotation);
+            if has_ground_below(&rapier_context, player, collider, next_position) {
+                position += step;
+            } else {
+                displacement -= step;
+                player_options.velocity *= Vec3::ONE - axis;
+            }
+        }
+    }
+
+    player_controller.translation = Some(displacement);
+}
+
+// Whether there is something to stand on right below the player at the position
+fn has_ground_below(
+    rapier_context: &RapierContext,
+    player: Entity,
+    collider: &Collider,
+    position: Transform,
+) -> bool {
+    // deeper drops than this are edges the sneaking player doesn't walk off
+    let max_drop = 0.5;
+    rapier_context
+        .cast_shape(
+            position.translation,
+            position.rotation,
+            -Vec3::Y,
+            collider,
+            max_drop,
+            true,
+            QueryFilter::only_fixed().exclude_collider(player),
+        )
+        .is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs the same input for the same amount of time at the tick rate,
+    // the player jumps off the ground on the first tick and never lands
+    fn displacement_at(tick_rate: u32, input: MovementInput, flying: bool) -> Vec3 {
+        let config = PlayerConfig::default();
+        let mut player = Player::default();
+        let dt = 1.0 / tick_rate as f32;
+        let mut displacement = Vec3::ZERO;
+        for tick in 0..tick_rate * 2 {
+            displacement += player.simulate(&input, &config, flying, tick == 0, dt);
+        }
+        displacement
+    }
+
+    fn assert_same_at_tick_rates(input: MovementInput, flying: bool) {
+        let expected = displacement_at(64, input, flying);
+        for tick_rate in [20, 30, 60, 144, 240] {
+            let displacement = displacement_at(tick_rate, input, flying);
+            assert!(
+                displacement.abs_diff_eq(expected, 1e-3),
+                "{tick_rate} ticks per second moved {displacement}, expected {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn walking_does_not_depend_on_tick_rate() {
+        let input = MovementInput {
+            direction: Vec3::new(1.0, 0.0, -1.0),
+            ..default()
+        };
+        assert_same_at_tick_rates(input, false);
+        assert_same_at_tick_rates(
+            MovementInput {
+                sprint: true,
+                ..input
+            },
+            false,
+        );
+        assert_same_at_tick_rates(
+            MovementInput {
+                sneak: true,
+                ..input
+            },
+            false,
+        );
+    }
+
+    #[test]
+    fn jumping_and_falling_do_not_depend_on_tick_rate() {
+        let input = MovementInput {
+            direction: Vec3::Z,
+            jump: true,
+            ..default()
+        };
+        assert_same_at_tick_rates(input, false);
+    }
+
+    #[test]
+    fn flying_does_not_depend_on_tick_rate() {
+        let input = MovementInput {
+            direction: Vec3::new(0.0, 1.0, 1.0),
+            ..default()
+        };
+        assert_same_at_tick_rates(input, true);
+    }
+
+    #[test]
+    fn falling_stops_at_terminal_velocity() {
+        let config = PlayerConfig::default();
+        let mut player = Player::default();
+        for _ in 0..64 * 10 {
+            player.simulate(&MovementInput::default(), &config, false, false, 1.0 / 64.0);
+        }
+        assert_eq!(player.velocity.y, -config.terminal_velocity);
+    }
+
+    #[test]
+    fn walking_reaches_walk_speed() {
+        let config = PlayerConfig::default();
+        let mut player = Player::default();
+        let input = MovementInput {
+            direction: Vec3::X,
+            ..default()
+        };
+        for _ in 0..64 * 2 {
+            player.simulate(&input, &config, false, true, 1.0 / 64.0);
+        }
+        assert!((player.velocity.x - config.walk_speed).abs() < 1e-3);
+        assert_eq!(player.velocity.z, 0.0);
+    }
+}
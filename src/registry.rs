@@ -1,15 +1,23 @@
-use std::hash::Hash;
+use std::{hash::Hash, sync::Arc};
 
 use bevy::{prelude::*, utils::HashMap};
+use thiserror::Error;
 
-use crate::block::{Block, BlockId};
+use crate::{
+    block::{
+        state::{format_state, parse_state, BlockProperties, BlockStateId},
+        Block, BlockId, NumericBlockId,
+    },
+    world::{generation::WorldGenerator, light::StateLight},
+};
 
 #[derive(Resource)]
 pub struct Registry<K, V> {
     pub registry: HashMap<K, V>,
 }
 
-pub type BlockRegistry = Registry<BlockId, Handle<Block>>;
+// World generators by their name
+pub type GeneratorRegistry = Registry<String, Arc<dyn WorldGenerator>>;
 
 impl<K: PartialEq + Eq + Hash, V> Registry<K, V> {
     pub fn new() -> Registry<K, V> {
@@ -22,17 +30,9 @@ impl<K: PartialEq + Eq + Hash, V> Registry<K, V> {
         self.registry.insert(k, v);
     }
 
-    pub fn remove(&mut self, k: &K) {
-        self.registry.remove(k);
-    }
-
     pub fn get(&self, k: &K) -> Option<&V> {
         self.registry.get(k)
     }
-
-    pub fn get_mut(&mut self, k: &K) -> Option<&mut V> {
-        self.registry.get_mut(k)
-    }
 }
 
 impl<K: PartialEq + Eq + Hash, V> Default for Registry<K, V> {
@@ -40,3 +40,183 @@ impl<K: PartialEq + Eq + Hash, V> Default for Registry<K, V> {
         Registry::new()
     }
 }
+
+#[derive(Debug, Error)]
+pub enum RegistryError {
+    #[error("No numeric ids are left for the block {0:?}")]
+    TooManyBlocks(BlockId),
+    #[error("No state ids are left for the {1} states of the block {0:?}")]
+    TooManyStates(BlockId, usize),
+}
+
+// Every registered block gets a compact numeric id, which is used at runtime
+// instead of the namespaced string id. Numeric ids are only valid until the game
+// is closed, so anything that is written to disk must use BlockId instead.
+// Same goes for the states of the blocks, every block gets a range of state ids,
+// one for every combination of its properties
+#[derive(Resource, Clone, Default)]
+pub struct BlockRegistry {
+    // indexed by numeric id
+    handles: Vec<Handle<Block>>,
+    names: Vec<BlockId>,
+    properties: Vec<BlockProperties>,
+    first_states: Vec<BlockStateId>,
+    numeric_ids: HashMap<BlockId, NumericBlockId>,
+    // block of every state and index of the state in the block, indexed by state id
+    states: Vec<(NumericBlockId, usize)>,
+    // indexed by state id, so chunks can be lit without the block assets
+    state_lights: Vec<StateLight>,
+}
+
+impl BlockRegistry {
+    pub fn register(
+        &mut self,
+        handle: Handle<Block>,
+        block: &Block,
+    ) -> Result<NumericBlockId, RegistryError> {
+        let id = block.id.clone();
+        let properties = block.properties.clone();
+        let state_lights = (0..properties.state_count()).map(|state| block.state_light(state));
+        if let Some(numeric_id) = self.numeric_ids.get(&id) {
+            // states of the block were already given out, so they can't change
+            if self.properties[numeric_id.0 as usize] != properties {
+                warn!("Properties of the block {:?} can't change at runtime", id);
+            } else {
+                let first = self.first_states[numeric_id.0 as usize].0 as usize;
+                for (i, state_light) in state_lights.enumerate() {
+                    self.state_lights[first + i] = state_light;
+                }
+            }
+            self.handles[numeric_id.0 as usize] = handle;
+            return Ok(*numeric_id);
+        }
+        let Ok(numeric_id) = u16::try_from(self.names.len()).map(NumericBlockId) else {
+            return Err(RegistryError::TooManyBlocks(id));
+        };
+        // every state of the block needs an id, not only the first one
+        let state_count = properties.state_count();
+        let (Ok(first_state), Ok(_)) = (
+            u16::try_from(self.states.len()),
+            u16::try_from(self.states.len() + state_count.saturating_sub(1)),
+        ) else {
+            return Err(RegistryError::TooManyStates(id, state_count));
+        };
+        self.first_states.push(BlockStateId(first_state));
+        self.states
+            .extend((0..properties.state_count()).map(|state| (numeric_id, state)));
+        self.state_lights.extend(state_lights);
+        self.handles.push(handle);
+        self.names.push(id.clone());
+        self.properties.push(properties);
+        self.numeric_ids.insert(id, numeric_id);
+        Ok(numeric_id)
+    }
+
+    pub fn get(&self, id: NumericBlockId) -> Option<&Handle<Block>> {
+        self.handles.get(id.0 as usize)
+    }
+
+    pub fn numeric_id(&self, id: &BlockId) -> Option<NumericBlockId> {
+        self.numeric_ids.get(id).copied()
+    }
+
+    pub fn block_id(&self, id: NumericBlockId) -> Option<&BlockId> {
+        self.names.get(id.0 as usize)
+    }
+
+    pub fn properties(&self, id: NumericBlockId) -> Option<&BlockProperties> {
+        self.properties.get(id.0 as usize)
+    }
+
+    // State with the index in the block
+    pub fn state(&self, id: NumericBlockId, state: usize) -> Option<BlockStateId> {
+        if state >= self.properties(id)?.state_count() {
+            return None;
+        }
+        let first = self.first_states.get(id.0 as usize)?;
+        Some(BlockStateId(first.0 + state as u16))
+    }
+
+    // State where all of the properties have their default values
+    pub fn default_state(&self, id: NumericBlockId) -> Option<BlockStateId> {
+        self.state(id, 0)
+    }
+
+    // Block of the state and index of the state in the block
+    pub fn block_of(&self, state: BlockStateId) -> Option<(NumericBlockId, usize)> {
+        self.states.get(state.0 as usize).copied()
+    }
+
+    pub fn state_light(&self, state: BlockStateId) -> StateLight {
+        self.state_lights
+            .get(state.0 as usize)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    // Block asset of the state and index of the state in the block
+    pub fn block_asset<'a>(
+        &self,
+        state: BlockStateId,
+        blocks: &'a Assets<Block>,
+    ) -> Option<(&'a Block, usize)> {
+        let (id, state) = self.block_of(state)?;
+        Some((blocks.get(self.get(id)?)?, state))
+    }
+
+    // Name of the state that doesn't change between runs, like BlockId
+    pub fn state_name(&self, state: BlockStateId) -> Option<String> {
+        let (id, state) = self.block_of(state)?;
+        let properties = self.properties(id)?;
+        Some(format_state(
+            &self.block_id(id)?.0,
+            properties.values(state),
+        ))
+    }
+
+    // Reverse of state_name, properties missing from the name get their default values
+    pub fn parse_state_name(&self, name: &str) -> Option<BlockStateId> {
+        let (id, values) = parse_state(name)?;
+        let id = self.numeric_id(&BlockId::from(id))?;
+        let state = self.properties(id)?.state(values)?;
+        self.state(id, state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::block::Opacity;
+
+    #[test]
+    fn blocks_without_free_state_ids_are_refused() {
+        let values: Vec<String> = (0..300).map(|i| i.to_string()).collect();
+        let block = |id: &str, names: &[&str]| {
+            let map: BTreeMap<String, Vec<String>> = names
+                .iter()
+                .map(|name| (name.to_string(), values.clone()))
+                .collect();
+            Block {
+                properties: BlockProperties::from(map),
+                ..Block::test_cube(id, Opacity::Opaque, 0)
+            }
+        };
+        let mut registry = BlockRegistry::default();
+        // 300 * 300 states don't fit into u16
+        let result = registry.register(Handle::default(), &block("mineclone:huge", &["a", "b"]));
+        assert!(matches!(
+            result,
+            Err(RegistryError::TooManyStates(_, 90000))
+        ));
+        assert!(registry
+            .numeric_id(&BlockId::from("mineclone:huge"))
+            .is_none());
+
+        let small = registry
+            .register(Handle::default(), &block("mineclone:small", &["a"]))
+            .unwrap();
+        assert_eq!(registry.default_state(small), Some(BlockStateId(0)));
+    }
+}
@@ -0,0 +1,82 @@
+use bevy::prelude::*;
+
+use crate::{
+    block::Block,
+    chunk::material::ChunkMaterial,
+    common::Atlas,
+    inventory::{Inventory, HOTBAR_SIZE},
+    player::Player,
+};
+
+use super::{
+    item::{spawn_item, ItemSource},
+    Ui, SLOT_SIZE, SLOT_TEXTURE,
+};
+
+// index of the selected slot texture in the UI atlas
+const SELECTED_SLOT_TEXTURE: usize = 2;
+
+// Frame of the hotbar slot with the index of the slot
+#[derive(Component)]
+pub struct HotbarSlot(pub usize);
+
+pub fn show_hotbar(
+    mut commands: Commands,
+    ui_atlas: Res<Atlas<Ui>>,
+    block_atlas: Res<Atlas<Block, ChunkMaterial>>,
+) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::FlexEnd,
+                padding: UiRect::bottom(Val::Px(8.0)),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for slot in 0..HOTBAR_SIZE {
+                parent
+                    .spawn((
+                        ImageBundle {
+                            style: Style {
+                                width: Val::Px(SLOT_SIZE),
+                                height: Val::Px(SLOT_SIZE),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            image: UiImage::new(ui_atlas.texture.clone()),
+                            ..default()
+                        },
+                        TextureAtlas {
+                            layout: ui_atlas.layout.clone(),
+                            index: SLOT_TEXTURE,
+                        },
+                        HotbarSlot(slot),
+                    ))
+                    .with_children(|parent| {
+                        spawn_item(parent, ItemSource::Slot(slot), &block_atlas);
+                    });
+            }
+        });
+}
+
+pub fn update_hotbar(
+    inventory_query: Query<&Inventory, (With<Player>, Changed<Inventory>)>,
+    mut slot_query: Query<(&HotbarSlot, &mut TextureAtlas)>,
+) {
+    let Ok(inventory) = inventory_query.get_single() else {
+        return;
+    };
+    for (HotbarSlot(slot), mut texture) in slot_query.iter_mut() {
+        texture.index = if *slot == inventory.selected {
+            SELECTED_SLOT_TEXTURE
+        } else {
+            SLOT_TEXTURE
+        };
+    }
+}
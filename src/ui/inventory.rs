@@ -0,0 +1,235 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    block::Block,
+    chunk::material::ChunkMaterial,
+    common::Atlas,
+    inventory::{Inventory, ItemStack, HOTBAR_SIZE, INVENTORY_SIZE},
+    player::Player,
+};
+
+use super::{
+    item::{spawn_item, ItemSource},
+    InInventory, Ui, SLOT_SIZE, SLOT_TEXTURE,
+};
+
+// Stack picked up from the inventory, which follows the cursor
+#[derive(Resource, Debug, Default)]
+pub struct HeldStack {
+    pub stack: Option<ItemStack>,
+    // slot the stack was picked up from, while the mouse button is still pressed
+    pub dragged_from: Option<usize>,
+}
+
+// Root of the inventory screen
+#[derive(Component)]
+pub struct InventoryScreen;
+
+// Clickable inventory slot with the index of the slot
+#[derive(Component)]
+pub struct InventorySlot(pub usize);
+
+// Node that moves together with the cursor
+#[derive(Component)]
+pub struct FollowCursor;
+
+pub fn show_inventory_screen(
+    mut commands: Commands,
+    ui_atlas: Res<Atlas<Ui>>,
+    block_atlas: Res<Atlas<Block, ChunkMaterial>>,
+) {
+    // main part of the inventory first and the hotbar in the bottom row
+    let rows: Vec<Vec<usize>> = (HOTBAR_SIZE..INVENTORY_SIZE)
+        .collect::<Vec<_>>()
+        .chunks(HOTBAR_SIZE)
+        .map(|row| row.to_vec())
+        .chain(std::iter::once((0..HOTBAR_SIZE).collect()))
+        .collect();
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            InventoryScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::all(Val::Px(8.0)),
+                        row_gap: Val::Px(2.0),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.2, 0.2, 0.2, 0.9).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for (i, row) in rows.iter().enumerate() {
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    flex_direction: FlexDirection::Row,
+                                    column_gap: Val::Px(2.0),
+                                    // separates the hotbar from the rest of the inventory
+                                    margin: UiRect::top(Val::Px(if i == rows.len() - 1 {
+                                        8.0
+                                    } else {
+                                        0.0
+                                    })),
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                for slot in row.iter() {
+                                    spawn_slot(parent, *slot, &ui_atlas, &block_atlas);
+                                }
+                            });
+                    }
+                });
+
+            // stack held on the cursor is drawn above everything else
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            width: Val::Px(SLOT_SIZE),
+                            height: Val::Px(SLOT_SIZE),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        z_index: ZIndex::Global(1),
+                        ..default()
+                    },
+                    FollowCursor,
+                ))
+                .with_children(|parent| {
+                    spawn_item(parent, ItemSource::Held, &block_atlas);
+                });
+        });
+}
+
+fn spawn_slot(
+    parent: &mut ChildBuilder,
+    slot: usize,
+    ui_atlas: &Atlas<Ui>,
+    block_atlas: &Atlas<Block, ChunkMaterial>,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(SLOT_SIZE),
+                    height: Val::Px(SLOT_SIZE),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                image: UiImage::new(ui_atlas.texture.clone()),
+                ..default()
+            },
+            TextureAtlas {
+                layout: ui_atlas.layout.clone(),
+                index: SLOT_TEXTURE,
+            },
+            InventorySlot(slot),
+        ))
+        .with_children(|parent| {
+            spawn_item(parent, ItemSource::Slot(slot), block_atlas);
+        });
+}
+
+// Shows or hides the inventory screen, when it gets closed
+// the held stack goes back to the inventory
+pub fn toggle_inventory_screen(
+    in_inventory: Res<InInventory>,
+    mut held: ResMut<HeldStack>,
+    mut screen_query: Query<&mut Visibility, With<InventoryScreen>>,
+    mut inventory_query: Query<&mut Inventory, With<Player>>,
+) {
+    let mut visibility = screen_query.single_mut();
+    match *in_inventory {
+        InInventory::In => *visibility = Visibility::Inherited,
+        InInventory::Out => {
+            *visibility = Visibility::Hidden;
+            held.dragged_from = None;
+            if let Some(stack) = held.stack.take() {
+                // whatever doesn't fit is lost
+                inventory_query.single_mut().add(stack.block, stack.count);
+            }
+        }
+    }
+}
+
+// Left click picks up, puts down or swaps stacks, dragging a stack to another slot
+// and releasing the button there puts it down too.
+// Shift + left click moves the stack between the hotbar and the rest of the inventory,
+// right click picks up half of the stack or puts down one block
+pub fn click_inventory_slots(
+    buttons: Res<ButtonInput<MouseButton>>,
+    k_input: Res<ButtonInput<KeyCode>>,
+    slot_query: Query<(&InventorySlot, &Interaction)>,
+    mut held: ResMut<HeldStack>,
+    mut inventory_query: Query<&mut Inventory, With<Player>>,
+) {
+    let hovered = slot_query
+        .iter()
+        .find(|(_, interaction)| **interaction != Interaction::None)
+        .map(|(InventorySlot(slot), _)| *slot);
+    if buttons.just_released(MouseButton::Left) {
+        let dragged_from = held.dragged_from.take();
+        // releasing over the same slot keeps the stack on the cursor, like a click
+        if let (Some(from), Some(slot)) = (dragged_from, hovered) {
+            if from != slot && held.stack.is_some() {
+                inventory_query
+                    .single_mut()
+                    .click_slot(slot, &mut held.stack);
+            }
+        }
+    }
+    let Some(slot) = hovered else {
+        return;
+    };
+
+    let shift = k_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if buttons.just_pressed(MouseButton::Left) {
+        let mut inventory = inventory_query.single_mut();
+        if shift {
+            inventory.quick_move(slot);
+        } else {
+            if held.stack.is_none() {
+                held.dragged_from = Some(slot);
+            }
+            inventory.click_slot(slot, &mut held.stack);
+        }
+    } else if buttons.just_pressed(MouseButton::Right) {
+        inventory_query
+            .single_mut()
+            .split_slot(slot, &mut held.stack);
+    }
+}
+
+pub fn follow_cursor(
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut node_query: Query<&mut Style, With<FollowCursor>>,
+) {
+    let Some(cursor) = window_query.single().cursor_position() else {
+        return;
+    };
+    for mut style in node_query.iter_mut() {
+        style.left = Val::Px(cursor.x - SLOT_SIZE / 2.0);
+        style.top = Val::Px(cursor.y - SLOT_SIZE / 2.0);
+    }
+}
@@ -0,0 +1,121 @@
+use bevy::prelude::*;
+
+use crate::{
+    block::Block,
+    chunk::material::ChunkMaterial,
+    common::Atlas,
+    inventory::{Inventory, ItemStack},
+    player::Player,
+    registry::BlockRegistry,
+};
+
+use super::inventory::HeldStack;
+
+const ICON_SIZE: f32 = 32.0;
+
+// Where the shown item comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ItemSource {
+    // slot of the player inventory
+    Slot(usize),
+    // stack held on the cursor in the inventory screen
+    Held,
+}
+
+// Texture of the block in the stack
+#[derive(Component)]
+pub struct ItemIcon(pub ItemSource);
+
+// How many blocks are in the stack
+#[derive(Component)]
+pub struct ItemCount(pub ItemSource);
+
+// Icon of the block with the count in the corner, both hidden while there is nothing to show
+pub fn spawn_item(
+    parent: &mut ChildBuilder,
+    source: ItemSource,
+    block_atlas: &Atlas<Block, ChunkMaterial>,
+) {
+    parent.spawn((
+        ImageBundle {
+            style: Style {
+                width: Val::Px(ICON_SIZE),
+                height: Val::Px(ICON_SIZE),
+                ..default()
+            },
+            image: UiImage::new(block_atlas.texture.clone()),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        TextureAtlas {
+            layout: block_atlas.layout.clone(),
+            index: 0,
+        },
+        ItemIcon(source),
+    ));
+    parent.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(4.0),
+                bottom: Val::Px(2.0),
+                ..default()
+            },
+            ..TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            )
+        },
+        ItemCount(source),
+    ));
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_items(
+    inventory_query: Query<Ref<Inventory>, With<Player>>,
+    held: Res<HeldStack>,
+    mut icon_query: Query<(&ItemIcon, &mut TextureAtlas, &mut Visibility)>,
+    mut count_query: Query<(&ItemCount, &mut Text)>,
+    block_registry: Res<BlockRegistry>,
+    blocks: Res<Assets<Block>>,
+    layouts: Res<Assets<TextureAtlasLayout>>,
+    block_atlas: Res<Atlas<Block, ChunkMaterial>>,
+) {
+    let Ok(inventory) = inventory_query.get_single() else {
+        return;
+    };
+    if !inventory.is_changed() && !held.is_changed() {
+        return;
+    }
+    let stack_of = |source: ItemSource| match source {
+        ItemSource::Slot(slot) => inventory.slots[slot],
+        ItemSource::Held => held.stack,
+    };
+
+    let block_layout = layouts.get(&block_atlas.layout).unwrap();
+    for (ItemIcon(source), mut texture, mut visibility) in icon_query.iter_mut() {
+        let index = stack_of(*source).and_then(|stack| {
+            let block = blocks.get(block_registry.get(stack.block)?)?;
+            block_layout.get_texture_index(*block.textures.front())
+        });
+        match index {
+            Some(index) => {
+                texture.index = index;
+                *visibility = Visibility::Inherited;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+
+    for (ItemCount(source), mut text) in count_query.iter_mut() {
+        text.sections[0].value = match stack_of(*source) {
+            // single block doesn't need a number
+            Some(ItemStack { count, .. }) if count > 1 => count.to_string(),
+            _ => String::new(),
+        };
+    }
+}
@@ -1,15 +1,23 @@
 use bevy::prelude::*;
 
-use self::systems::*;
+use self::{hotbar::*, inventory::*, item::*, systems::*};
 use crate::common::{AppState, SetupState};
 
+mod hotbar;
+mod inventory;
+mod item;
 mod systems;
 
+// index of the inventory slot texture in the UI atlas
+const SLOT_TEXTURE: usize = 1;
+const SLOT_SIZE: f32 = 48.0;
+
 pub struct UiPlugin;
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<InInventory>()
+            .init_resource::<HeldStack>()
             .add_systems(
                 OnEnter(AppState::Setup(SetupState::Ui)),
                 load_ui_texture_atlas,
@@ -19,7 +27,15 @@ impl Plugin for UiPlugin {
                 check_textures.run_if(in_state(AppState::Setup(SetupState::Ui))),
             )
             .add_systems(OnExit(AppState::Setup(SetupState::Ui)), add_ui_atlas)
-            .add_systems(Update, cursor_grab_toggle.run_if(in_state(AppState::Game)))
+            .add_systems(
+                Update,
+                (
+                    cursor_grab_toggle,
+                    toggle_inventory_screen.run_if(resource_changed::<InInventory>),
+                )
+                    .chain()
+                    .run_if(in_state(AppState::Game)),
+            )
             .add_systems(
                 Update,
                 recenter_cursor.run_if(
@@ -29,7 +45,24 @@ impl Plugin for UiPlugin {
                         .and_then(resource_equals(InInventory::Out)),
                 ),
             )
-            .add_systems(OnEnter(AppState::Game), (show_crosshair, cursor_grab));
+            .add_systems(
+                Update,
+                (click_inventory_slots, follow_cursor)
+                    .run_if(in_state(AppState::Game).and_then(resource_equals(InInventory::In))),
+            )
+            .add_systems(
+                Update,
+                (update_hotbar, update_items).run_if(in_state(AppState::Game)),
+            )
+            .add_systems(
+                OnEnter(AppState::Game),
+                (
+                    show_crosshair,
+                    show_hotbar,
+                    show_inventory_screen,
+                    cursor_grab,
+                ),
+            );
     }
 }
 
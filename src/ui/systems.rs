@@ -9,6 +9,7 @@ use bevy::{
 use crate::{
     common::{AppState, Atlas, SetupState},
     config::GameConfig,
+    game_mode::GameMode,
 };
 
 use super::{InInventory, Ui, UiTextures};
@@ -41,7 +42,7 @@ pub fn add_ui_atlas(
     let image = textures.get_mut(&ui_textures.0).unwrap();
     image.sampler = ImageSampler::nearest();
 
-    commands.insert_resource(Atlas {
+    commands.insert_resource(Atlas::<Ui> {
         texture: ui_textures.0.clone(),
         layout: texture_layouts.add(atlas_layout),
         material: None,
@@ -86,7 +87,12 @@ pub fn cursor_grab_toggle(
     mut in_inventory: ResMut<InInventory>,
     k_input: Res<ButtonInput<KeyCode>>,
     config: Res<GameConfig>,
+    game_mode: Res<GameMode>,
 ) {
+    // spectator has no inventory, but can still close it after switching the mode
+    if *in_inventory == InInventory::Out && !game_mode.can_interact() {
+        return;
+    }
     if k_input.just_pressed(config.key_config.game_controls.inventory_key) {
         let mut primary_window = q_window.single_mut();
         match *in_inventory {
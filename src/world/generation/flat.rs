@@ -0,0 +1,44 @@
+use crate::{
+    chunk::{Chunk, ChunkDimensions, ChunkTranslation},
+    registry::BlockRegistry,
+};
+
+use super::{generate_from_heightmap, WorldGenerator};
+
+// Endless plain with the grass at the same height everywhere
+#[derive(Clone, Debug)]
+pub struct FlatGenerator {
+    pub height: isize,
+    pub dirt_depth: isize,
+}
+
+impl Default for FlatGenerator {
+    fn default() -> Self {
+        FlatGenerator {
+            height: 0,
+            dirt_depth: 3,
+        }
+    }
+}
+
+impl WorldGenerator for FlatGenerator {
+    fn generate_chunk(
+        &self,
+        chunk_translation: ChunkTranslation,
+        dimensions: ChunkDimensions,
+        _seed: u32,
+        registry: &BlockRegistry,
+    ) -> Chunk {
+        generate_from_heightmap(
+            chunk_translation,
+            dimensions,
+            self.dirt_depth,
+            registry,
+            |_, _| self.height,
+        )
+    }
+
+    fn surface_height(&self, _x: isize, _z: isize, _seed: u32) -> Option<isize> {
+        Some(self.height)
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{
+    block::{state::BlockStateId, BlockId},
+    chunk::{Chunk, ChunkDimensions, ChunkTranslation},
+    registry::BlockRegistry,
+};
+
+pub use self::{flat::FlatGenerator, noise::NoiseGenerator, void::VoidGenerator};
+
+mod flat;
+mod noise;
+mod void;
+
+// Everything that can fill a chunk with blocks
+// Generators must be deterministic, same input must always give the same chunk
+pub trait WorldGenerator: Send + Sync {
+    fn generate_chunk(
+        &self,
+        chunk_translation: ChunkTranslation,
+        dimensions: ChunkDimensions,
+        seed: u32,
+        registry: &BlockRegistry,
+    ) -> Chunk;
+
+    // Global y of the topmost solid block at the given global x and z,
+    // None if there is no ground at all
+    fn surface_height(&self, x: isize, z: isize, seed: u32) -> Option<isize>;
+}
+
+// Fills chunk column by column using surface height of every column,
+// grass on top, then dirt_depth blocks of dirt and stone till the bottom
+fn generate_from_heightmap<F>(
+    chunk_translation: ChunkTranslation,
+    dimensions: ChunkDimensions,
+    dirt_depth: isize,
+    registry: &BlockRegistry,
+    height_at: F,
+) -> Chunk
+where
+    F: Fn(isize, isize) -> isize,
+{
+    let mut chunk = Chunk::new(chunk_translation, dimensions);
+    let column_blocks = ColumnBlocks::new(registry);
+
+    // global coordinates of the chunk corner with the lowest coordinates
+    let origin_x = chunk_translation.x * dimensions.width as isize;
+    let origin_y = chunk_translation.y * dimensions.height as isize;
+    let origin_z = chunk_translation.z * dimensions.depth as isize;
+
+    for x in 0..dimensions.width {
+        for z in 0..dimensions.depth {
+            let height = height_at(origin_x + x as isize, origin_z + z as isize);
+            // whole column is above the surface, it's all air
+            if height < origin_y {
+                continue;
+            }
+            for y in 0..dimensions.height {
+                let index = x * dimensions.width * dimensions.height + y * dimensions.width + z;
+                chunk.set_block_by_index(
+                    index,
+                    column_blocks.block_at(origin_y + y as isize, height, dirt_depth),
+                );
+            }
+        }
+    }
+
+    // chunks deep underground are all stone
+    chunk.block_data.compact();
+    chunk
+}
+
+// Blocks terrain columns are made of
+struct ColumnBlocks {
+    grass: Option<BlockStateId>,
+    dirt: Option<BlockStateId>,
+    stone: Option<BlockStateId>,
+}
+
+impl ColumnBlocks {
+    fn new(registry: &BlockRegistry) -> ColumnBlocks {
+        ColumnBlocks {
+            grass: ColumnBlocks::default_state(registry, "mineclone:grass"),
+            dirt: ColumnBlocks::default_state(registry, "mineclone:dirt"),
+            stone: ColumnBlocks::default_state(registry, "mineclone:stone"),
+        }
+    }
+
+    fn default_state(registry: &BlockRegistry, id: &str) -> Option<BlockStateId> {
+        registry
+            .numeric_id(&BlockId::from(id))
+            .and_then(|id| registry.default_state(id))
+    }
+
+    // Which block should be at the global y, given the surface height of the column
+    fn block_at(&self, y: isize, height: isize, dirt_depth: isize) -> Option<BlockStateId> {
+        if y > height {
+            None
+        } else if y == height {
+            self.grass
+        } else if y >= height - dirt_depth {
+            self.dirt
+        } else {
+            self.stone
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+
+use crate::{
+    chunk::{Chunk, ChunkDimensions, ChunkTranslation},
+    config::WorldGenConfig,
+    registry::BlockRegistry,
+};
+
+use super::{generate_from_heightmap, WorldGenerator};
+
+// Heightmap based terrain with hills and valleys made of layered perlin noise
+#[derive(Clone, Debug)]
+pub struct NoiseGenerator {
+    octaves: usize,
+    scale: f64,
+    persistence: f64,
+    lacunarity: f64,
+    base_height: isize,
+    amplitude: f64,
+    dirt_depth: isize,
+}
+
+impl NoiseGenerator {
+    pub fn new(config: &WorldGenConfig) -> NoiseGenerator {
+        NoiseGenerator {
+            octaves: config.octaves,
+            scale: config.scale,
+            persistence: config.persistence,
+            lacunarity: config.lacunarity,
+            base_height: config.base_height,
+            amplitude: config.amplitude,
+            dirt_depth: config.dirt_depth,
+        }
+    }
+
+    fn noise(&self, seed: u32) -> Fbm<Perlin> {
+        Fbm::<Perlin>::new(seed)
+            .set_octaves(self.octaves)
+            .set_frequency(1.0 / self.scale)
+            .set_persistence(self.persistence)
+            .set_lacunarity(self.lacunarity)
+    }
+
+    fn height_at(&self, noise: &Fbm<Perlin>, x: isize, z: isize) -> isize {
+        let value = noise.get([x as f64, z as f64]);
+        self.base_height + (value * self.amplitude).round() as isize
+    }
+}
+
+impl WorldGenerator for NoiseGenerator {
+    fn generate_chunk(
+        &self,
+        chunk_translation: ChunkTranslation,
+        dimensions: ChunkDimensions,
+        seed: u32,
+        registry: &BlockRegistry,
+    ) -> Chunk {
+        let noise = self.noise(seed);
+        generate_from_heightmap(
+            chunk_translation,
+            dimensions,
+            self.dirt_depth,
+            registry,
+            |x, z| self.height_at(&noise, x, z),
+        )
+    }
+
+    fn surface_height(&self, x: isize, z: isize, seed: u32) -> Option<isize> {
+        Some(self.height_at(&self.noise(seed), x, z))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::Handle;
+
+    use crate::block::{Block, Opacity};
+
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_same_chunk() {
+        let generator = NoiseGenerator::new(&WorldGenConfig::default());
+        let mut registry = BlockRegistry::default();
+        for name in ["mineclone:grass", "mineclone:dirt", "mineclone:stone"] {
+            registry
+                .register(
+                    Handle::default(),
+                    &Block::test_cube(name, Opacity::Opaque, 0),
+                )
+                .unwrap();
+        }
+        let dims = ChunkDimensions {
+            width: 16,
+            height: 16,
+            depth: 16,
+        };
+        // chunk at the base height, so it has both ground and air in it
+        let translation = ChunkTranslation { x: 3, y: 0, z: -2 };
+        let blocks = |seed| {
+            let chunk = generator.generate_chunk(translation, dims, seed, &registry);
+            (0..chunk.block_data.len())
+                .map(|i| chunk.get_block_by_index(i))
+                .collect::<Vec<_>>()
+        };
+
+        let first = blocks(42);
+        assert!(first.iter().any(Option::is_some));
+        assert!(first.iter().any(Option::is_none));
+        assert_eq!(first, blocks(42));
+        assert_ne!(first, blocks(43));
+    }
+}
@@ -0,0 +1,26 @@
+use crate::{
+    chunk::{Chunk, ChunkDimensions, ChunkTranslation},
+    registry::BlockRegistry,
+};
+
+use super::WorldGenerator;
+
+// Nothing but air
+#[derive(Clone, Debug, Default)]
+pub struct VoidGenerator;
+
+impl WorldGenerator for VoidGenerator {
+    fn generate_chunk(
+        &self,
+        chunk_translation: ChunkTranslation,
+        dimensions: ChunkDimensions,
+        _seed: u32,
+        _registry: &BlockRegistry,
+    ) -> Chunk {
+        Chunk::new(chunk_translation, dimensions)
+    }
+
+    fn surface_height(&self, _x: isize, _z: isize, _seed: u32) -> Option<isize> {
+        None
+    }
+}
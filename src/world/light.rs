@@ -0,0 +1,595 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use super::GameWorld;
+use crate::{
+    chunk::{mesh::Face, Chunk, ChunkDimensions, ChunkTranslation},
+    registry::BlockRegistry,
+};
+
+pub const MAX_LIGHT: u8 = 15;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightKind {
+    // comes from the open sky above, falls down without getting dimmer
+    Sky,
+    // comes from the blocks that emit light
+    Block,
+}
+
+impl LightKind {
+    pub const ALL: [LightKind; 2] = [LightKind::Sky, LightKind::Block];
+}
+
+// How the block state interacts with light,
+// it's kept in the registry, so chunks can be lit in the background
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StateLight {
+    pub emission: u8,
+    // light doesn't go through the block
+    pub opaque: bool,
+}
+
+// Light of every block in the chunk, same indices as in the block data.
+// Both kinds share one byte, sky light in the high half and block light in the low one
+#[derive(Clone, Debug)]
+pub struct LightStorage(Vec<u8>);
+
+impl LightStorage {
+    pub fn new(len: usize) -> LightStorage {
+        LightStorage(vec![0; len])
+    }
+
+    pub fn get(&self, index: usize, kind: LightKind) -> u8 {
+        match kind {
+            LightKind::Sky => self.0[index] >> 4,
+            LightKind::Block => self.0[index] & 0x0f,
+        }
+    }
+
+    pub fn set(&mut self, index: usize, kind: LightKind, level: u8) {
+        let value = &mut self.0[index];
+        *value = match kind {
+            LightKind::Sky => (*value & 0x0f) | (level << 4),
+            LightKind::Block => (*value & 0xf0) | level,
+        };
+    }
+}
+
+// How bright the block looks with the light level, every level is 20% dimmer than the one above
+pub fn brightness(level: u8) -> f32 {
+    0.8f32.powi((MAX_LIGHT - level.min(MAX_LIGHT)) as i32)
+}
+
+// Blocks the light spreads through, positions are global
+trait LightVolume {
+    // None outside of the volume
+    fn state_light(&self, pos: IVec3) -> Option<StateLight>;
+    fn light(&self, pos: IVec3, kind: LightKind) -> Option<u8>;
+    fn set_light(&mut self, pos: IVec3, kind: LightKind, level: u8);
+}
+
+// Light the neighbour in the direction gets from the block
+fn spread(kind: LightKind, level: u8, direction: IVec3) -> u8 {
+    if kind == LightKind::Sky && level == MAX_LIGHT && direction == IVec3::NEG_Y {
+        MAX_LIGHT
+    } else {
+        level.saturating_sub(1)
+    }
+}
+
+// Breadth first flood fill from the queued blocks, making everything around them brighter
+fn spread_light<V: LightVolume>(volume: &mut V, kind: LightKind, mut queue: VecDeque<IVec3>) {
+    while let Some(pos) = queue.pop_front() {
+        let Some(level) = volume.light(pos, kind).filter(|level| *level > 1) else {
+            continue;
+        };
+        for face in Face::ALL {
+            let direction = face.normal();
+            let next = pos + direction;
+            // light doesn't go into opaque blocks and unloaded chunks
+            if volume
+                .state_light(next)
+                .map_or(true, |state_light| state_light.opaque)
+            {
+                continue;
+            }
+            let next_level = spread(kind, level, direction);
+            if volume.light(next, kind).is_some_and(|l| l < next_level) {
+                volume.set_light(next, kind, next_level);
+                queue.push_back(next);
+            }
+        }
+    }
+}
+
+// Takes away the light of the blocks and everything that was lit by it,
+// then fills the dark area back with the light of its surroundings
+fn remove_light<V: LightVolume>(volume: &mut V, kind: LightKind, sources: Vec<IVec3>) {
+    let mut removed = VecDeque::new();
+    let mut relight = VecDeque::new();
+    for pos in sources {
+        if let Some(level) = volume.light(pos, kind).filter(|level| *level > 0) {
+            volume.set_light(pos, kind, 0);
+            removed.push_back((pos, level));
+        }
+    }
+    while let Some((pos, level)) = removed.pop_front() {
+        for face in Face::ALL {
+            let direction = face.normal();
+            let next = pos + direction;
+            let Some(next_level) = volume.light(next, kind).filter(|level| *level > 0) else {
+                continue;
+            };
+            // dimmer neighbour could only get its light from the removed block
+            if next_level < level || spread(kind, level, direction) == MAX_LIGHT {
+                volume.set_light(next, kind, 0);
+                removed.push_back((next, next_level));
+                // blocks emitting light keep it
+                let emission = volume.state_light(next).map_or(0, |light| light.emission);
+                if kind == LightKind::Block && emission > 0 {
+                    volume.set_light(next, kind, emission);
+                    relight.push_back(next);
+                }
+            } else {
+                relight.push_back(next);
+            }
+        }
+    }
+    spread_light(volume, kind, relight);
+}
+
+// Single chunk being lit on its own
+struct ChunkVolume<'a> {
+    chunk: &'a mut Chunk,
+    dimensions: ChunkDimensions,
+    registry: &'a BlockRegistry,
+}
+
+impl ChunkVolume<'_> {
+    fn index(&self, pos: IVec3) -> Option<usize> {
+        let (translation, index) = locate(pos, self.dimensions);
+        (translation == self.chunk.translation).then_some(index)
+    }
+}
+
+impl LightVolume for ChunkVolume<'_> {
+    fn state_light(&self, pos: IVec3) -> Option<StateLight> {
+        let index = self.index(pos)?;
+        Some(state_light(self.chunk, index, self.registry))
+    }
+
+    fn light(&self, pos: IVec3, kind: LightKind) -> Option<u8> {
+        Some(self.chunk.light.get(self.index(pos)?, kind))
+    }
+
+    fn set_light(&mut self, pos: IVec3, kind: LightKind, level: u8) {
+        if let Some(index) = self.index(pos) {
+            self.chunk.light.set(index, kind, level);
+        }
+    }
+}
+
+// All loaded chunks, remembering the ones whose light changed
+struct WorldVolume<'a> {
+    chunks: &'a mut HashMap<ChunkTranslation, Chunk>,
+    dimensions: ChunkDimensions,
+    registry: &'a BlockRegistry,
+    relit_chunks: &'a mut HashSet<ChunkTranslation>,
+}
+
+impl LightVolume for WorldVolume<'_> {
+    fn state_light(&self, pos: IVec3) -> Option<StateLight> {
+        let (translation, index) = locate(pos, self.dimensions);
+        let chunk = self.chunks.get(&translation)?;
+        Some(state_light(chunk, index, self.registry))
+    }
+
+    fn light(&self, pos: IVec3, kind: LightKind) -> Option<u8> {
+        let (translation, index) = locate(pos, self.dimensions);
+        Some(self.chunks.get(&translation)?.light.get(index, kind))
+    }
+
+    fn set_light(&mut self, pos: IVec3, kind: LightKind, level: u8) {
+        let (translation, index) = locate(pos, self.dimensions);
+        if let Some(chunk) = self.chunks.get_mut(&translation) {
+            chunk.light.set(index, kind, level);
+            self.relit_chunks.insert(translation);
+        }
+    }
+}
+
+// Chunk of the global block position and index of the block in it
+fn locate(pos: IVec3, dimensions: ChunkDimensions) -> (ChunkTranslation, usize) {
+    let size = IVec3::new(
+        dimensions.width as i32,
+        dimensions.height as i32,
+        dimensions.depth as i32,
+    );
+    let translation = pos.div_euclid(size);
+    let local = pos.rem_euclid(size).as_uvec3();
+    let index = local.x as usize * dimensions.width * dimensions.height
+        + local.y as usize * dimensions.width
+        + local.z as usize;
+    (
+        ChunkTranslation {
+            x: translation.x as isize,
+            y: translation.y as isize,
+            z: translation.z as isize,
+        },
+        index,
+    )
+}
+
+fn state_light(chunk: &Chunk, index: usize, registry: &BlockRegistry) -> StateLight {
+    chunk
+        .get_block_by_index(index)
+        .map_or(StateLight::default(), |state| registry.state_light(state))
+}
+
+// Global position of the chunk corner with the lowest coordinates
+fn chunk_origin(translation: ChunkTranslation, dimensions: ChunkDimensions) -> IVec3 {
+    IVec3::new(
+        (translation.x * dimensions.width as isize) as i32,
+        (translation.y * dimensions.height as isize) as i32,
+        (translation.z * dimensions.depth as isize) as i32,
+    )
+}
+
+// Lights the chunk on its own: sky light falls down the columns that are open to the sky
+// and spreads sideways, blocks emitting light light up their surroundings.
+// Light coming from the neighbours is added once the chunk is in the world.
+// is_open_sky takes local x and z of the column and tells whether sky light gets to its top
+pub fn light_chunk<F>(
+    chunk: &mut Chunk,
+    dimensions: ChunkDimensions,
+    registry: &BlockRegistry,
+    is_open_sky: F,
+) where
+    F: Fn(usize, usize) -> bool,
+{
+    let origin = chunk_origin(chunk.translation, dimensions);
+    let mut volume = ChunkVolume {
+        chunk,
+        dimensions,
+        registry,
+    };
+    let mut sky = VecDeque::new();
+    let mut block = VecDeque::new();
+    for x in 0..dimensions.width {
+        for z in 0..dimensions.depth {
+            let mut open = is_open_sky(x, z);
+            for y in (0..dimensions.height).rev() {
+                let pos = origin + IVec3::new(x as i32, y as i32, z as i32);
+                let state_light = volume.state_light(pos).unwrap_or_default();
+                open &= !state_light.opaque;
+                if open {
+                    volume.set_light(pos, LightKind::Sky, MAX_LIGHT);
+                    sky.push_back(pos);
+                }
+                if state_light.emission > 0 {
+                    volume.set_light(pos, LightKind::Block, state_light.emission);
+                    block.push_back(pos);
+                }
+            }
+        }
+    }
+    spread_light(&mut volume, LightKind::Sky, sky);
+    spread_light(&mut volume, LightKind::Block, block);
+}
+
+impl GameWorld {
+    fn light_volume<'a>(&'a mut self, registry: &'a BlockRegistry) -> WorldVolume<'a> {
+        WorldVolume {
+            chunks: &mut self.chunk_data,
+            dimensions: self.chunk_dimensions,
+            registry,
+            relit_chunks: &mut self.relit_chunks,
+        }
+    }
+
+    // Connects light of the chunk that was just loaded with the light of its loaded neighbours
+    pub(super) fn merge_light(
+        &mut self,
+        chunk_translation: ChunkTranslation,
+        registry: &BlockRegistry,
+    ) {
+        let dimensions = self.chunk_dimensions;
+        let origin = chunk_origin(chunk_translation, dimensions);
+        let mut volume = self.light_volume(registry);
+
+        // blocks on both sides of every border of the chunk
+        let mut border = Vec::new();
+        for face in Face::ALL {
+            let (n, a, b) = face.axes();
+            let size = [dimensions.width, dimensions.height, dimensions.depth];
+            let normal = face.normal();
+            let layer = if normal[n] > 0 { size[n] as i32 - 1 } else { 0 };
+            for i in 0..size[a] as i32 {
+                for j in 0..size[b] as i32 {
+                    let mut local = IVec3::ZERO;
+                    local[n] = layer;
+                    local[a] = i;
+                    local[b] = j;
+                    border.push((origin + local, origin + local + normal));
+                }
+            }
+        }
+
+        // sky light of the columns is only a guess until the chunk above is loaded,
+        // light falling through the border where nothing falls from above is wrong
+        let wrong_sky: Vec<IVec3> = border
+            .iter()
+            .flat_map(|(inside, outside)| [(*inside, *outside), (*outside, *inside)])
+            .filter(|(lower, upper)| *upper - *lower == IVec3::Y)
+            .filter(|(lower, upper)| {
+                volume.light(*lower, LightKind::Sky) == Some(MAX_LIGHT)
+                    && volume
+                        .light(*upper, LightKind::Sky)
+                        .is_some_and(|level| level < MAX_LIGHT)
+            })
+            .map(|(lower, _)| lower)
+            .collect();
+        remove_light(&mut volume, LightKind::Sky, wrong_sky);
+
+        // light goes both ways through the borders
+        for kind in LightKind::ALL {
+            let queue = border
+                .iter()
+                .flat_map(|(inside, outside)| [*inside, *outside])
+                .collect();
+            spread_light(&mut volume, kind, queue);
+        }
+    }
+
+    // Updates light around the block that was just changed
+    pub(super) fn update_light(&mut self, pos: IVec3, registry: &BlockRegistry) {
+        let mut volume = self.light_volume(registry);
+        let Some(state_light) = volume.state_light(pos) else {
+            return;
+        };
+        for kind in LightKind::ALL {
+            remove_light(&mut volume, kind, vec![pos]);
+            let mut queue = VecDeque::new();
+            if kind == LightKind::Block && state_light.emission > 0 {
+                volume.set_light(pos, kind, state_light.emission);
+                queue.push_back(pos);
+            }
+            // light comes back from the neighbours, unless the block stops it
+            if !state_light.opaque {
+                queue.extend(Face::ALL.map(|face| pos + face.normal()));
+            }
+            spread_light(&mut volume, kind, queue);
+        }
+    }
+
+    // Chunks whose light changed since the last call, their meshes are out of date
+    pub fn take_relit_chunks(&mut self) -> HashSet<ChunkTranslation> {
+        std::mem::take(&mut self.relit_chunks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        block::{state::BlockStateId, Block, Opacity},
+        world::{generation::VoidGenerator, region::RegionStorage},
+    };
+
+    // Blocks in a small box, opaque where the function says so,
+    // the only emitting block is at emitter
+    struct TestVolume {
+        size: IVec3,
+        opaque: fn(IVec3) -> bool,
+        emitter: Option<IVec3>,
+        light: HashMap<(IVec3, bool), u8>,
+    }
+
+    impl TestVolume {
+        fn new(size: i32, opaque: fn(IVec3) -> bool, emitter: Option<IVec3>) -> TestVolume {
+            TestVolume {
+                size: IVec3::splat(size),
+                opaque,
+                emitter,
+                light: HashMap::new(),
+            }
+        }
+
+        fn inside(&self, pos: IVec3) -> bool {
+            pos.cmpge(IVec3::ZERO).all() && pos.cmplt(self.size).all()
+        }
+    }
+
+    impl LightVolume for TestVolume {
+        fn state_light(&self, pos: IVec3) -> Option<StateLight> {
+            self.inside(pos).then(|| StateLight {
+                emission: if self.emitter == Some(pos) { 14 } else { 0 },
+                opaque: (self.opaque)(pos),
+            })
+        }
+
+        fn light(&self, pos: IVec3, kind: LightKind) -> Option<u8> {
+            self.inside(pos).then(|| {
+                self.light
+                    .get(&(pos, kind == LightKind::Sky))
+                    .copied()
+                    .unwrap_or(0)
+            })
+        }
+
+        fn set_light(&mut self, pos: IVec3, kind: LightKind, level: u8) {
+            self.light.insert((pos, kind == LightKind::Sky), level);
+        }
+    }
+
+    #[test]
+    fn block_light_fades_with_distance() {
+        let emitter = IVec3::splat(8);
+        let mut volume = TestVolume::new(16, |_| false, Some(emitter));
+        volume.set_light(emitter, LightKind::Block, 14);
+        spread_light(&mut volume, LightKind::Block, VecDeque::from([emitter]));
+        assert_eq!(volume.light(emitter + IVec3::X, LightKind::Block), Some(13));
+        assert_eq!(
+            volume.light(emitter + IVec3::new(2, -3, 1), LightKind::Block),
+            Some(8)
+        );
+
+        // removing the only source makes everything dark again
+        volume.emitter = None;
+        remove_light(&mut volume, LightKind::Block, vec![emitter]);
+        assert!(volume.light.values().all(|level| *level == 0));
+    }
+
+    #[test]
+    fn sky_light_falls_down_and_goes_around_walls() {
+        // roof over the half of the box with x < 8, one block above the floor
+        let mut volume = TestVolume::new(16, |pos| pos.y == 12 && pos.x < 8, None);
+        let top: VecDeque<IVec3> = (0..16)
+            .flat_map(|x| (0..16).map(move |z| IVec3::new(x, 15, z)))
+            .collect();
+        for pos in top.iter() {
+            volume.set_light(*pos, LightKind::Sky, MAX_LIGHT);
+        }
+        spread_light(&mut volume, LightKind::Sky, top);
+        assert_eq!(
+            volume.light(IVec3::new(12, 0, 3), LightKind::Sky),
+            Some(MAX_LIGHT)
+        );
+        // under the roof light comes from the side, dimmer the farther from the edge
+        assert_eq!(volume.light(IVec3::new(7, 0, 3), LightKind::Sky), Some(14));
+        assert_eq!(volume.light(IVec3::new(3, 0, 3), LightKind::Sky), Some(10));
+    }
+
+    // Empty world that is never saved, with a stone and a torch emitting 14
+    fn world() -> (GameWorld, BlockRegistry, [Option<BlockStateId>; 2]) {
+        let mut registry = BlockRegistry::default();
+        let states = [
+            ("mineclone:stone", Opacity::Opaque, 0),
+            ("mineclone:torch", Opacity::Transparent(0), 14),
+        ]
+        .map(|(id, opacity, emission)| {
+            let id = registry
+                .register(Handle::default(), &Block::test_cube(id, opacity, emission))
+                .unwrap();
+            registry.default_state(id)
+        });
+        let world = GameWorld {
+            chunk_data: HashMap::new(),
+            chunk_dimensions: ChunkDimensions::default(),
+            generator: Arc::new(VoidGenerator),
+            seed: 0,
+            storage: RegionStorage::new(std::env::temp_dir()),
+            dirty_chunks: HashSet::new(),
+            relit_chunks: HashSet::new(),
+        };
+        (world, registry, states)
+    }
+
+    // Lit the same way chunks coming from ChunkSource are
+    fn chunk<F>(
+        world: &GameWorld,
+        registry: &BlockRegistry,
+        translation: ChunkTranslation,
+        is_open_sky: bool,
+        fill: F,
+    ) -> Chunk
+    where
+        F: FnOnce(&mut Chunk),
+    {
+        let mut chunk = Chunk::new(translation, world.chunk_dimensions);
+        fill(&mut chunk);
+        light_chunk(&mut chunk, world.chunk_dimensions, registry, |_, _| {
+            is_open_sky
+        });
+        chunk
+    }
+
+    fn light_at(world: &GameWorld, pos: IVec3, kind: LightKind) -> u8 {
+        let (translation, index) = locate(pos, world.chunk_dimensions);
+        world.chunk_data[&translation].light.get(index, kind)
+    }
+
+    #[test]
+    fn block_light_crosses_chunk_borders() {
+        let (mut world, registry, [_, torch]) = world();
+        let torch_pos = IVec3::new(15, 8, 8);
+        let left = ChunkTranslation { x: 0, y: 0, z: 0 };
+        let right = ChunkTranslation { x: 1, y: 0, z: 0 };
+        // dark chunk first, so the light only gets to it when the torch chunk is merged
+        let chunk_right = chunk(&world, &registry, right, false, |_| ());
+        world.insert_chunk(chunk_right, &registry);
+        let chunk_left = chunk(&world, &registry, left, false, |chunk| {
+            chunk.set_block_at(torch_pos.as_vec3(), torch, ChunkDimensions::default());
+        });
+        world.insert_chunk(chunk_left, &registry);
+        assert_eq!(
+            light_at(&world, torch_pos + IVec3::new(2, 0, 0), LightKind::Block),
+            12
+        );
+        assert_eq!(
+            light_at(&world, torch_pos + IVec3::new(3, 1, 0), LightKind::Block),
+            10
+        );
+        world.take_relit_chunks();
+
+        // light that came through the border goes away with the torch
+        world.set_block_at(None, torch_pos.as_vec3(), &registry);
+        assert_eq!(
+            light_at(&world, torch_pos + IVec3::new(2, 0, 0), LightKind::Block),
+            0
+        );
+        assert_eq!(
+            light_at(&world, torch_pos + IVec3::new(3, 1, 0), LightKind::Block),
+            0
+        );
+        assert!(world.take_relit_chunks().contains(&right));
+    }
+
+    #[test]
+    fn roof_in_the_chunk_above_shades_the_chunk_below() {
+        let (mut world, registry, [stone, _]) = world();
+        let below = ChunkTranslation { x: 0, y: 0, z: 0 };
+        let above = ChunkTranslation { x: 0, y: 1, z: 0 };
+        // saved chunks let the sky into every column, until the chunk above says otherwise
+        let chunk_below = chunk(&world, &registry, below, true, |_| ());
+        world.insert_chunk(chunk_below, &registry);
+        assert_eq!(
+            light_at(&world, IVec3::new(4, 0, 4), LightKind::Sky),
+            MAX_LIGHT
+        );
+
+        // roof over x < 8 at the bottom of the chunk above
+        let chunk_above = chunk(&world, &registry, above, true, |chunk| {
+            for x in 0..8 {
+                for z in 0..16 {
+                    chunk.set_block_at(
+                        Vec3::new(x as f32, 16.0, z as f32),
+                        stone,
+                        ChunkDimensions::default(),
+                    );
+                }
+            }
+        });
+        world.insert_chunk(chunk_above, &registry);
+        assert_eq!(
+            light_at(&world, IVec3::new(12, 0, 4), LightKind::Sky),
+            MAX_LIGHT
+        );
+        assert_eq!(light_at(&world, IVec3::new(7, 0, 4), LightKind::Sky), 14);
+        assert_eq!(light_at(&world, IVec3::new(3, 0, 4), LightKind::Sky), 10);
+
+        // digging a hole in the roof lets the sky back in, down to the bottom
+        world.set_block_at(None, Vec3::new(3.0, 16.0, 4.0), &registry);
+        assert_eq!(
+            light_at(&world, IVec3::new(3, 0, 4), LightKind::Sky),
+            MAX_LIGHT
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{config::GameConfig, game_mode::GameMode};
+
+const META_FILE: &str = "world.json";
+
+#[derive(Debug, Error)]
+pub enum MetaError {
+    #[error("Could not access world metadata: {0}")]
+    Io(#[from] io::Error),
+    #[error("World metadata is corrupted: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+// Everything about the world that is not stored in chunks
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WorldMeta {
+    #[serde(default)]
+    pub game_mode: GameMode,
+    // ticks since the world was created
+    #[serde(default)]
+    pub time: u64,
+    // None for worlds saved before the seed was stored with them
+    #[serde(default)]
+    pub seed: Option<u32>,
+}
+
+// Metadata of the world as it was on disk when the game started, None for new worlds
+// Loaded only once, everything saved with the world starts from it
+#[derive(Resource, Debug)]
+pub struct SavedWorldMeta(pub Option<WorldMeta>);
+
+impl FromWorld for SavedWorldMeta {
+    fn from_world(world: &mut World) -> Self {
+        let config = world.resource::<GameConfig>();
+        match WorldMeta::load(&config.save_config.world_dir) {
+            Ok(meta) => SavedWorldMeta(meta),
+            Err(e) => {
+                error!("Could not load world metadata: {}", e);
+                SavedWorldMeta(None)
+            }
+        }
+    }
+}
+
+impl WorldMeta {
+    // None if the world was never saved before
+    pub fn load(world_dir: &Path) -> Result<Option<WorldMeta>, MetaError> {
+        let json = match fs::read_to_string(meta_path(world_dir)) {
+            Ok(json) => json,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(serde_json::from_str(&json)?))
+    }
+
+    pub fn save(&self, world_dir: &Path) -> Result<(), MetaError> {
+        fs::create_dir_all(world_dir)?;
+        // same as with regions, temporary file keeps the old metadata intact on crash
+        let path = meta_path(world_dir);
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+}
+
+fn meta_path(world_dir: &Path) -> PathBuf {
+    world_dir.join(META_FILE)
+}
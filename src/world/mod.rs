@@ -1,22 +1,59 @@
-use bevy::{prelude::*, utils::HashMap};
+use std::sync::Arc;
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
 
 use crate::{
-    block::BlockId,
+    block::state::BlockStateId,
     chunk::{Chunk, ChunkDimensions, ChunkTranslation},
     common::AppState,
+    config::GameConfig,
+    registry::{BlockRegistry, GeneratorRegistry},
 };
 
-use self::systems::*;
+use self::{
+    generation::{FlatGenerator, NoiseGenerator, VoidGenerator, WorldGenerator},
+    light::light_chunk,
+    meta::SavedWorldMeta,
+    raycast::{raycast, RayHit},
+    region::{RegionError, RegionStorage},
+    systems::*,
+    time::WorldTime,
+};
 
+pub mod generation;
+pub mod light;
+pub mod meta;
+pub mod raycast;
+pub mod region;
 mod systems;
+pub mod time;
 
 pub struct GameWorldPlugin;
 
 impl Plugin for GameWorldPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<GameWorld>()
+        let config = &app.world.resource::<GameConfig>().world_gen_config;
+        let mut generators = GeneratorRegistry::new();
+        generators.register(String::from("flat"), Arc::new(FlatGenerator::default()));
+        generators.register(String::from("void"), Arc::new(VoidGenerator));
+        generators.register(String::from("noise"), Arc::new(NoiseGenerator::new(config)));
+
+        // GameWorld picks its generator from the registry and its seed from the saved
+        // metadata, so they must be inserted first
+        app.insert_resource(generators)
+            .init_resource::<SavedWorldMeta>()
+            .init_resource::<GameWorld>()
+            .init_resource::<AutosaveTimer>()
+            .init_resource::<WorldTime>()
             .add_systems(OnEnter(AppState::Game), setup_global_light)
-            .add_systems(Update, (day_night_cycle).run_if(in_state(AppState::Game)));
+            .add_systems(
+                Update,
+                (day_night_cycle, autosave).run_if(in_state(AppState::Game)),
+            )
+            .add_systems(Last, save_on_exit.run_if(in_state(AppState::Game)));
     }
 }
 
@@ -24,10 +61,52 @@ impl Plugin for GameWorldPlugin {
 pub struct GameWorld {
     pub chunk_data: HashMap<ChunkTranslation, Chunk>,
     pub chunk_dimensions: ChunkDimensions,
+    pub generator: Arc<dyn WorldGenerator>,
+    pub seed: u32,
+    pub storage: RegionStorage,
+    // chunks changed by the player since they were last saved
+    pub dirty_chunks: HashSet<ChunkTranslation>,
+    // chunks whose light changed, their meshes need to be rebuilt
+    relit_chunks: HashSet<ChunkTranslation>,
+}
+
+#[derive(Resource)]
+pub struct AutosaveTimer(pub Timer);
+
+impl FromWorld for AutosaveTimer {
+    fn from_world(world: &mut World) -> Self {
+        let config = &world.resource::<GameConfig>().save_config;
+        AutosaveTimer(Timer::from_seconds(
+            config.autosave_interval,
+            TimerMode::Repeating,
+        ))
+    }
 }
 
-impl Default for GameWorld {
-    fn default() -> Self {
+impl FromWorld for GameWorld {
+    fn from_world(world: &mut World) -> Self {
+        let save_config = &world.resource::<GameConfig>().save_config;
+        let storage = RegionStorage::new(save_config.world_dir.clone());
+        let config = &world.resource::<GameConfig>().world_gen_config;
+        let generators = world.resource::<GeneratorRegistry>();
+        let generator = match generators.get(&config.generator) {
+            Some(generator) => generator.clone(),
+            None => {
+                warn!(
+                    "Unknown world generator {:?}, falling back to \"noise\"",
+                    config.generator
+                );
+                generators.get(&String::from("noise")).unwrap().clone()
+            }
+        };
+        // saved chunks were generated with the world's seed, so the one
+        // from the config is only used for new worlds
+        let seed = world
+            .resource::<SavedWorldMeta>()
+            .0
+            .as_ref()
+            .and_then(|meta| meta.seed)
+            .unwrap_or(config.seed);
         GameWorld {
             chunk_data: HashMap::new(),
             chunk_dimensions: ChunkDimensions {
@@ -35,69 +114,199 @@ impl Default for GameWorld {
                 height: 16,
                 depth: 16,
             },
+            generator,
+            seed,
+            storage,
+            dirty_chunks: HashSet::new(),
+            relit_chunks: HashSet::new(),
         }
     }
 }
 
 impl GameWorld {
-    pub fn get_block_at(&mut self, pos: Vec3) -> Option<BlockId> {
+    pub fn get_block_at(&mut self, pos: Vec3, registry: &BlockRegistry) -> Option<BlockStateId> {
         let chunk_translation = ChunkTranslation::get_chunk_translation(pos, self.chunk_dimensions);
         let chunk_dimensions = self.chunk_dimensions;
-        let chunk = self.get_chunk_at(chunk_translation);
+        let chunk = self.get_chunk_at(chunk_translation, registry);
         chunk.get_block_at(pos, chunk_dimensions)
     }
 
+    // First block hit by the ray, that is not farther than max_distance
+    pub fn raycast(
+        &mut self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        registry: &BlockRegistry,
+    ) -> Option<RayHit> {
+        raycast(origin, direction, max_distance, |pos| {
+            self.get_block_at(pos.as_vec3(), registry).is_some()
+        })
+    }
+
     // Sets block at position to the new one returning what was there previously
-    pub fn set_block_at(&mut self, block_id: Option<BlockId>, pos: Vec3) -> Option<BlockId> {
+    pub fn set_block_at(
+        &mut self,
+        block_state: Option<BlockStateId>,
+        pos: Vec3,
+        registry: &BlockRegistry,
+    ) -> Option<BlockStateId> {
         let chunk_translation = ChunkTranslation::get_chunk_translation(pos, self.chunk_dimensions);
         let chunk_dimensions = self.chunk_dimensions;
-        let chunk = self.get_chunk_at_mut(chunk_translation);
-        chunk.set_block_at(pos, block_id, chunk_dimensions)
+        self.dirty_chunks.insert(chunk_translation);
+        let chunk = self.get_chunk_at_mut(chunk_translation, registry);
+        let previous = chunk.set_block_at(pos, block_state, chunk_dimensions);
+        self.update_light(pos.floor().as_ivec3(), registry);
+        previous
     }
 
-    // Generates chunk and applies player changes to it
+    // Loads chunk saved on disk, or generates it if it was never modified
     pub fn get_chunk_at_mut(
         &mut self,
         chunk_translation: ChunkTranslation,
-        // modified data to apply while generating
+        registry: &BlockRegistry,
     ) -> &mut Chunk {
-        let dimensions = self.chunk_dimensions;
-        let chunk = self.chunk_data.entry(chunk_translation).or_insert({
-            let mut unique_blocks = Vec::new();
-            let mut block_data =
-                vec![None; dimensions.width * dimensions.height * dimensions.depth];
-
-            for x in 0..dimensions.width {
-                for y in 0..dimensions.height {
-                    for z in 0..dimensions.depth {
-                        let index =
-                            x * dimensions.width * dimensions.height + y * dimensions.width + z;
-                        // if there is a chunk in memory already
-                        // TODO replace with actual world generation
-                        // if we're at chunks that are under 0 then generate stone
-                        if chunk_translation.y < 0 {
-                            block_data[index] = Some(BlockId::from("mineclone:stone"));
-                            if !unique_blocks.contains(&BlockId::from("mineclone:stone")) {
-                                unique_blocks.push(BlockId::from("mineclone:stone"));
-                            }
-                        } else {
-                            // else generate air
-                            block_data[index] = None;
-                        }
-                    }
-                }
-            }
+        if !self.chunk_data.contains_key(&chunk_translation) {
+            let chunk = self.chunk_source().load(chunk_translation, registry);
+            self.chunk_data.insert(chunk_translation, chunk);
+            self.merge_light(chunk_translation, registry);
+        }
+        self.chunk_data.get_mut(&chunk_translation).unwrap()
+    }
 
-            Chunk {
-                block_data,
-                unique_blocks,
-                translation: chunk_translation,
-            }
-        });
-        chunk
+    // Everything needed to load or generate chunks away from the main thread
+    pub fn chunk_source(&self) -> ChunkSource {
+        ChunkSource {
+            generator: self.generator.clone(),
+            seed: self.seed,
+            storage: self.storage.clone(),
+            dimensions: self.chunk_dimensions,
+        }
+    }
+
+    pub fn is_loaded(&self, chunk_translation: ChunkTranslation) -> bool {
+        self.chunk_data.contains_key(&chunk_translation)
+    }
+
+    // Adds chunk loaded in the background, unless the chunk got loaded in the meantime
+    pub fn insert_chunk(&mut self, chunk: Chunk, registry: &BlockRegistry) {
+        let chunk_translation = chunk.translation;
+        if self.is_loaded(chunk_translation) {
+            return;
+        }
+        self.chunk_data.insert(chunk_translation, chunk);
+        self.merge_light(chunk_translation, registry);
+    }
+
+    pub fn get_chunk_at(
+        &mut self,
+        chunk_translation: ChunkTranslation,
+        registry: &BlockRegistry,
+    ) -> &Chunk {
+        self.get_chunk_at_mut(chunk_translation, registry)
+    }
+
+    // Chunk together with all chunks touching it, in ChunkTranslation::with_surrounding order,
+    // None if any of them is not loaded yet
+    pub fn get_loaded_chunk_with_neighbours(
+        &self,
+        chunk_translation: ChunkTranslation,
+    ) -> Option<[&Chunk; 27]> {
+        let mut chunks = Vec::with_capacity(27);
+        for translation in chunk_translation.with_surrounding() {
+            chunks.push(self.chunk_data.get(&translation)?);
+        }
+        Some(chunks.try_into().unwrap())
+    }
+
+    // Writes all modified chunks to disk
+    pub fn save_dirty_chunks(&mut self, registry: &BlockRegistry) -> Result<(), RegionError> {
+        let chunks = self
+            .dirty_chunks
+            .iter()
+            .filter_map(|translation| self.chunk_data.get(translation));
+        self.storage.save_chunks(chunks, registry)?;
+        self.dirty_chunks.clear();
+        Ok(())
     }
 
-    pub fn get_chunk_at(&mut self, chunk_translation: ChunkTranslation) -> &Chunk {
-        self.get_chunk_at_mut(chunk_translation)
+    // Frees memory taken by the chunks, saving the modified ones first,
+    // all chunks of one region are saved together so the region is rewritten only once
+    pub fn unload_chunks(
+        &mut self,
+        chunk_translations: &[ChunkTranslation],
+        registry: &BlockRegistry,
+    ) -> Result<(), RegionError> {
+        let dirty = chunk_translations
+            .iter()
+            .filter(|translation| self.dirty_chunks.contains(*translation))
+            .filter_map(|translation| self.chunk_data.get(translation));
+        self.storage.save_chunks(dirty, registry)?;
+        for chunk_translation in chunk_translations {
+            self.dirty_chunks.remove(chunk_translation);
+            self.chunk_data.remove(chunk_translation);
+        }
+        Ok(())
+    }
+
+    // Frees all chunks for which is_needed returns false, saving the modified ones
+    pub fn retain_chunks<F>(
+        &mut self,
+        is_needed: F,
+        registry: &BlockRegistry,
+    ) -> Result<(), RegionError>
+    where
+        F: Fn(ChunkTranslation) -> bool,
+    {
+        let unneeded: Vec<ChunkTranslation> = self
+            .chunk_data
+            .keys()
+            .filter(|t| !is_needed(**t))
+            .copied()
+            .collect();
+        self.unload_chunks(&unneeded, registry)
+    }
+}
+
+#[derive(Clone)]
+pub struct ChunkSource {
+    generator: Arc<dyn WorldGenerator>,
+    seed: u32,
+    storage: RegionStorage,
+    dimensions: ChunkDimensions,
+}
+
+impl ChunkSource {
+    // Loads chunk saved on disk, or generates it if it was never modified
+    pub fn load(&self, chunk_translation: ChunkTranslation, registry: &BlockRegistry) -> Chunk {
+        let saved = self
+            .storage
+            .load_chunk(chunk_translation, self.dimensions, registry)
+            .unwrap_or_else(|e| {
+                warn!("Could not load chunk {:?}: {}", chunk_translation, e);
+                None
+            });
+        let Some(mut chunk) = saved else {
+            let mut chunk = self.generator.generate_chunk(
+                chunk_translation,
+                self.dimensions,
+                self.seed,
+                registry,
+            );
+            // sky light gets into the generated chunk where the ground is below it
+            let top = (chunk_translation.y + 1) * self.dimensions.height as isize;
+            let min_x = chunk_translation.x * self.dimensions.width as isize;
+            let min_z = chunk_translation.z * self.dimensions.depth as isize;
+            light_chunk(&mut chunk, self.dimensions, registry, |x, z| {
+                self.generator
+                    .surface_height(min_x + x as isize, min_z + z as isize, self.seed)
+                    .map_or(true, |height| height < top)
+            });
+            return chunk;
+        };
+        // the player could have dug or built anything above the saved chunk, so sky light
+        // is let into every column and merging with the chunk above takes the wrong part away
+        light_chunk(&mut chunk, self.dimensions, registry, |_, _| true);
+        chunk
     }
 }
@@ -0,0 +1,175 @@
+use bevy::prelude::*;
+
+// Block hit by the ray
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    // global position of the block corner with the lowest coordinates
+    pub block_pos: Vec3,
+    // normal of the face the ray entered the block through,
+    // zero if the ray started inside of the block
+    pub normal: Vec3,
+    // distance from the ray origin to the point where the ray entered the block
+    pub distance: f32,
+}
+
+// Amanatides & Woo voxel traversal, visits every block the ray goes through in order
+// and stops at the first one that is solid
+pub fn raycast<F>(
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+    mut is_solid: F,
+) -> Option<RayHit>
+where
+    F: FnMut(IVec3) -> bool,
+{
+    let direction = direction.normalize_or_zero();
+    if direction == Vec3::ZERO {
+        return None;
+    }
+
+    let mut block = origin.floor().as_ivec3();
+    // which way the ray goes along every axis
+    let step = IVec3::new(
+        axis_step(direction.x),
+        axis_step(direction.y),
+        axis_step(direction.z),
+    );
+    // distance along the ray between two block borders of the axis
+    let t_delta = Vec3::new(
+        (1.0 / direction.x).abs(),
+        (1.0 / direction.y).abs(),
+        (1.0 / direction.z).abs(),
+    );
+    // distance along the ray to the next block border of the axis
+    let mut t_max = Vec3::new(
+        first_border(origin.x, direction.x, block.x),
+        first_border(origin.y, direction.y, block.y),
+        first_border(origin.z, direction.z, block.z),
+    );
+
+    let mut distance = 0.0;
+    let mut normal = Vec3::ZERO;
+    loop {
+        if is_solid(block) {
+            return Some(RayHit {
+                block_pos: block.as_vec3(),
+                normal,
+                distance,
+            });
+        }
+        let axis = if t_max.x < t_max.y {
+            if t_max.x < t_max.z {
+                0
+            } else {
+                2
+            }
+        } else if t_max.y < t_max.z {
+            1
+        } else {
+            2
+        };
+        distance = t_max[axis];
+        if distance > max_distance {
+            return None;
+        }
+        block[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+        normal = Vec3::ZERO;
+        normal[axis] = -step[axis] as f32;
+    }
+}
+
+fn axis_step(direction: f32) -> i32 {
+    if direction > 0.0 {
+        1
+    } else if direction < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+fn first_border(origin: f32, direction: f32, block: i32) -> f32 {
+    if direction > 0.0 {
+        (block as f32 + 1.0 - origin) / direction
+    } else if direction < 0.0 {
+        (origin - block as f32) / -direction
+    } else {
+        f32::INFINITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CENTER: Vec3 = Vec3::splat(0.5);
+
+    #[test]
+    fn hit_normal_faces_back_along_the_axis() {
+        let directions = [
+            Vec3::X,
+            Vec3::NEG_X,
+            Vec3::Y,
+            Vec3::NEG_Y,
+            Vec3::Z,
+            Vec3::NEG_Z,
+        ];
+        for direction in directions {
+            let target = (direction * 3.0).as_ivec3();
+            let hit = raycast(CENTER, direction, 10.0, |block| block == target).unwrap();
+            assert_eq!(hit.block_pos, target.as_vec3(), "direction {}", direction);
+            assert_eq!(hit.normal, -direction, "direction {}", direction);
+            assert_eq!(hit.distance, 2.5, "direction {}", direction);
+        }
+    }
+
+    #[test]
+    fn negative_directions_enter_through_the_far_side() {
+        let direction = Vec3::new(-1.0, -0.3, -0.2);
+        let hit = raycast(CENTER, direction, 10.0, |block| block.x <= -3).unwrap();
+        assert_eq!(hit.block_pos.x, -3.0);
+        assert_eq!(hit.normal, Vec3::X);
+        // the ray crosses x = -2 on the way in
+        let expected = 2.5 / direction.normalize().x.abs();
+        assert!((hit.distance - expected).abs() < 1e-5);
+
+        let hit = raycast(CENTER, direction, 10.0, |block| block.y <= -2).unwrap();
+        assert_eq!(hit.block_pos.y, -2.0);
+        assert_eq!(hit.normal, Vec3::Y);
+    }
+
+    #[test]
+    fn axis_parallel_ray_stays_in_its_row() {
+        let origin = Vec3::new(0.2, 7.9, -4.6);
+        let mut visited = Vec::new();
+        let hit = raycast(origin, Vec3::NEG_Z, 10.0, |block| {
+            visited.push(block);
+            false
+        });
+        assert!(hit.is_none());
+        assert!(visited.iter().all(|block| block.x == 0 && block.y == 7));
+        assert_eq!(visited.first().unwrap().z, -5);
+        assert_eq!(visited.last().unwrap().z, -15);
+    }
+
+    #[test]
+    fn ray_starting_inside_of_a_block_hits_it_right_away() {
+        let origin = Vec3::new(1.2, 5.7, -3.4);
+        let hit = raycast(origin, Vec3::new(0.3, -1.0, 0.5), 10.0, |_| true).unwrap();
+        assert_eq!(hit.block_pos, Vec3::new(1.0, 5.0, -4.0));
+        assert_eq!(hit.normal, Vec3::ZERO);
+        assert_eq!(hit.distance, 0.0);
+    }
+
+    #[test]
+    fn blocks_past_max_distance_are_not_hit() {
+        let is_solid = |block: IVec3| block.x == 5;
+        // the block is entered 4.5 blocks away from the origin
+        assert!(raycast(CENTER, Vec3::X, 4.4, is_solid).is_none());
+        assert!(raycast(CENTER, Vec3::X, 4.6, is_solid).is_some());
+        assert!(raycast(CENTER, Vec3::X, 100.0, |_| false).is_none());
+        assert!(raycast(CENTER, Vec3::ZERO, 100.0, |_| true).is_none());
+    }
+}
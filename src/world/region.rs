@@ -0,0 +1,356 @@
+use std::{
+    fs::{self, File},
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+    path::PathBuf,
+};
+
+use bevy::{log::warn, utils::HashMap};
+use thiserror::Error;
+
+use crate::{
+    chunk::{Chunk, ChunkDimensions, ChunkTranslation},
+    registry::BlockRegistry,
+};
+
+// How many chunks are stored in one region file along every axis
+pub const REGION_SIZE: isize = 32;
+
+const REGION_MAGIC: &[u8; 4] = b"MCRG";
+const REGION_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum RegionError {
+    #[error("Could not access region file: {0}")]
+    Io(#[from] io::Error),
+    #[error("Region file is corrupted: {0}")]
+    Corrupted(&'static str),
+    #[error("Block state {0} is not in the registry")]
+    UnknownBlock(u16),
+}
+
+// Coordinates of the region, every region holds REGION_SIZE^3 chunks
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct RegionTranslation {
+    pub x: isize,
+    pub y: isize,
+    pub z: isize,
+}
+
+impl RegionTranslation {
+    pub fn of_chunk(chunk_translation: ChunkTranslation) -> RegionTranslation {
+        RegionTranslation {
+            x: chunk_translation.x.div_euclid(REGION_SIZE),
+            y: chunk_translation.y.div_euclid(REGION_SIZE),
+            z: chunk_translation.z.div_euclid(REGION_SIZE),
+        }
+    }
+}
+
+// Index of the chunk inside of its region's offset table
+fn chunk_index(chunk_translation: ChunkTranslation) -> u32 {
+    let x = chunk_translation.x.rem_euclid(REGION_SIZE);
+    let y = chunk_translation.y.rem_euclid(REGION_SIZE);
+    let z = chunk_translation.z.rem_euclid(REGION_SIZE);
+    ((x * REGION_SIZE + y) * REGION_SIZE + z) as u32
+}
+
+// Region file layout(all numbers are little endian):
+// magic "MCRG", version: u32, entry count: u32,
+// entry count times (chunk index: u32, offset: u32, length: u32),
+// after that chunk data at the offsets from the table
+#[derive(Clone, Debug)]
+pub struct RegionStorage {
+    pub root: PathBuf,
+}
+
+impl RegionStorage {
+    pub fn new(root: impl Into<PathBuf>) -> RegionStorage {
+        RegionStorage { root: root.into() }
+    }
+
+    fn region_path(&self, region: RegionTranslation) -> PathBuf {
+        self.root
+            .join(format!("r.{}.{}.{}.region", region.x, region.y, region.z))
+    }
+
+    // Reads chunk data of all chunks in the region by their index
+    fn read_region(&self, region: RegionTranslation) -> Result<HashMap<u32, Vec<u8>>, RegionError> {
+        let bytes = match fs::read(self.region_path(region)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let entries = read_header(&mut bytes.as_slice())?;
+        let mut chunks = HashMap::with_capacity(entries.len());
+        for entry in entries {
+            let data = bytes
+                .get(entry.offset as usize..entry.offset as usize + entry.length as usize)
+                .ok_or(RegionError::Corrupted("chunk is out of file bounds"))?;
+            chunks.insert(entry.index, data.to_vec());
+        }
+        Ok(chunks)
+    }
+
+    fn write_region(
+        &self,
+        region: RegionTranslation,
+        chunks: &HashMap<u32, Vec<u8>>,
+    ) -> Result<(), RegionError> {
+        let header_len = REGION_MAGIC.len() + 4 + 4 + chunks.len() * 12;
+        let mut header = Vec::with_capacity(header_len);
+        let mut data = Vec::new();
+        header.extend_from_slice(REGION_MAGIC);
+        header.extend_from_slice(&REGION_VERSION.to_le_bytes());
+        header.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+        for (index, chunk) in chunks.iter() {
+            header.extend_from_slice(&index.to_le_bytes());
+            header.extend_from_slice(&((header_len + data.len()) as u32).to_le_bytes());
+            header.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            data.extend_from_slice(chunk);
+        }
+        header.extend(data);
+
+        fs::create_dir_all(&self.root)?;
+        // writing to the temporary file first, so crash in the middle
+        // of saving doesn't corrupt the whole region
+        let path = self.region_path(region);
+        let tmp_path = path.with_extension("region.tmp");
+        fs::write(&tmp_path, header)?;
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    // Reads only the header and the data of the chunk, not the whole region
+    pub fn load_chunk(
+        &self,
+        chunk_translation: ChunkTranslation,
+        dimensions: ChunkDimensions,
+        registry: &BlockRegistry,
+    ) -> Result<Option<Chunk>, RegionError> {
+        let path = self.region_path(RegionTranslation::of_chunk(chunk_translation));
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let index = chunk_index(chunk_translation);
+        let Some(entry) = read_header(&mut file)?
+            .into_iter()
+            .find(|entry| entry.index == index)
+        else {
+            return Ok(None);
+        };
+        file.seek(SeekFrom::Start(entry.offset as u64))?;
+        let mut data = vec![0; entry.length as usize];
+        read_exact(&mut file, &mut data)?;
+        decode_chunk(&data, chunk_translation, dimensions, registry).map(Some)
+    }
+
+    // Saves chunks, rewriting every affected region only once
+    pub fn save_chunks<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = &'a Chunk>,
+        registry: &BlockRegistry,
+    ) -> Result<(), RegionError> {
+        let mut regions: HashMap<RegionTranslation, Vec<&Chunk>> = HashMap::new();
+        for chunk in chunks {
+            regions
+                .entry(RegionTranslation::of_chunk(chunk.translation))
+                .or_default()
+                .push(chunk);
+        }
+        for (region, chunks) in regions {
+            let mut region_data = self.read_region(region)?;
+            for chunk in chunks {
+                region_data.insert(
+                    chunk_index(chunk.translation),
+                    encode_chunk(chunk, registry)?,
+                );
+            }
+            self.write_region(region, &region_data)?;
+        }
+        Ok(())
+    }
+}
+
+// Where the chunk is in the region file
+struct RegionEntry {
+    index: u32,
+    offset: u32,
+    length: u32,
+}
+
+// Reads the offset table, leaving the reader right after it
+fn read_header(reader: &mut impl Read) -> Result<Vec<RegionEntry>, RegionError> {
+    let mut start = [0; 12];
+    read_exact(reader, &mut start)?;
+    let mut start = ByteReader::new(&start);
+    if start.take(4)? != REGION_MAGIC {
+        return Err(RegionError::Corrupted("wrong magic"));
+    }
+    if start.u32()? != REGION_VERSION {
+        return Err(RegionError::Corrupted("unsupported version"));
+    }
+    let count = start.u32()? as usize;
+    if count > (REGION_SIZE * REGION_SIZE * REGION_SIZE) as usize {
+        return Err(RegionError::Corrupted("too many chunks"));
+    }
+    let mut table = vec![0; count * 12];
+    read_exact(reader, &mut table)?;
+    let mut table = ByteReader::new(&table);
+    (0..count)
+        .map(|_| {
+            Ok(RegionEntry {
+                index: table.u32()?,
+                offset: table.u32()?,
+                length: table.u32()?,
+            })
+        })
+        .collect()
+}
+
+fn read_exact(reader: &mut impl Read, buf: &mut [u8]) -> Result<(), RegionError> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        ErrorKind::UnexpectedEof => RegionError::Corrupted("unexpected end of data"),
+        _ => e.into(),
+    })
+}
+
+// Chunk layout(all numbers are little endian):
+// palette length: u16, palette length times (state length: u16, state: utf8 bytes),
+// then one u16 per block, 0 is air and i is palette[i - 1]
+// Palette stores state names like "mineclone:log[axis=x]", since state ids can change between runs,
+// names without properties are the default states, same as the plain block ids of older saves
+fn encode_chunk(chunk: &Chunk, registry: &BlockRegistry) -> Result<Vec<u8>, RegionError> {
+    let mut bytes = Vec::with_capacity(chunk.block_data.len() * 2);
+    bytes.extend_from_slice(&(chunk.unique_blocks.len() as u16).to_le_bytes());
+    for state in chunk.unique_blocks.iter() {
+        let name = registry
+            .state_name(*state)
+            .ok_or(RegionError::UnknownBlock(state.0))?;
+        bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+    }
+    for index in chunk.block_data.iter() {
+        bytes.extend_from_slice(&index.to_le_bytes());
+    }
+    Ok(bytes)
+}
+
+fn decode_chunk(
+    bytes: &[u8],
+    chunk_translation: ChunkTranslation,
+    dimensions: ChunkDimensions,
+    registry: &BlockRegistry,
+) -> Result<Chunk, RegionError> {
+    let mut reader = ByteReader::new(bytes);
+    let mut chunk = Chunk::new(chunk_translation, dimensions);
+    let palette_len = reader.u16()?;
+    let mut palette = Vec::with_capacity(palette_len as usize + 1);
+    palette.push(None);
+    for _ in 0..palette_len {
+        let len = reader.u16()? as usize;
+        let name = std::str::from_utf8(reader.take(len)?)
+            .map_err(|_| RegionError::Corrupted("block state is not utf8"))?;
+        let state = registry.parse_state_name(name);
+        if state.is_none() {
+            warn!(
+                "Unknown block state {:?} in {:?}, replacing with air",
+                name, chunk_translation
+            );
+        }
+        palette.push(state);
+    }
+    for index in 0..chunk.block_data.len() {
+        let block = palette
+            .get(reader.u16()? as usize)
+            .ok_or(RegionError::Corrupted("block is not in the palette"))?;
+        chunk.set_block_by_index(index, *block);
+    }
+    chunk.block_data.compact();
+    Ok(chunk)
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(bytes: &'a [u8]) -> ByteReader<'a> {
+        ByteReader { bytes, position: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], RegionError> {
+        let slice = self
+            .bytes
+            .get(self.position..self.position + len)
+            .ok_or(RegionError::Corrupted("unexpected end of data"))?;
+        self.position += len;
+        Ok(slice)
+    }
+
+    fn u16(&mut self) -> Result<u16, RegionError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, RegionError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::Handle;
+
+    use super::*;
+    use crate::block::{Block, Opacity};
+
+    #[test]
+    fn chunks_are_loaded_back_one_by_one() {
+        let dims = ChunkDimensions {
+            width: 16,
+            height: 16,
+            depth: 16,
+        };
+        let root = std::env::temp_dir().join(format!("mineclone-region-{}", std::process::id()));
+        let storage = RegionStorage::new(&root);
+        let mut registry = BlockRegistry::default();
+        let stone = registry
+            .register(
+                Handle::default(),
+                &Block::test_cube("mineclone:stone", Opacity::Opaque, 0),
+            )
+            .unwrap();
+        let stone = registry.default_state(stone);
+
+        // both chunks end up in the same region, with a block at a different index
+        let chunks: Vec<Chunk> = (0..2)
+            .map(|i| {
+                let mut chunk = Chunk::new(ChunkTranslation { x: i, y: 0, z: 0 }, dims);
+                chunk.set_block_by_index(i as usize, stone);
+                chunk
+            })
+            .collect();
+        storage.save_chunks(&chunks, &registry).unwrap();
+
+        let blocks = |chunk: &Chunk| {
+            (0..chunk.block_data.len())
+                .map(|i| chunk.get_block_by_index(i))
+                .collect::<Vec<_>>()
+        };
+        for chunk in chunks.iter() {
+            let loaded = storage
+                .load_chunk(chunk.translation, dims, &registry)
+                .unwrap()
+                .unwrap();
+            assert_eq!(blocks(&loaded), blocks(chunk));
+        }
+        let missing = ChunkTranslation { x: 5, y: 0, z: 0 };
+        assert!(storage
+            .load_chunk(missing, dims, &registry)
+            .unwrap()
+            .is_none());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -1,16 +1,128 @@
-use bevy::prelude::*;
+use bevy::{app::AppExit, prelude::*};
+
+use super::{meta::WorldMeta, time::WorldTime, AutosaveTimer, GameWorld};
+use crate::{
+    block::Block, chunk::material::ChunkMaterial, common::Atlas, game_mode::GameMode,
+    registry::BlockRegistry,
+};
+
+// illuminance of the sun at noon
+const SUN_ILLUMINANCE: f32 = 3000.0;
+// how much of the sky light is left at night, the moon still lights the world a bit
+const NIGHT_SKY_LIGHT: f32 = 0.2;
+const DAY_SKY: Color = Color::rgb(0.47, 0.66, 1.0);
+const NIGHT_SKY: Color = Color::rgb(0.01, 0.01, 0.04);
+// color of the sun and the sky while the sun is near the horizon
+const SUNSET: Color = Color::rgb(1.0, 0.55, 0.3);
+
+// Directional light moved around by the day and night cycle
+#[derive(Component)]
+pub struct Sun;
 
 pub fn setup_global_light(mut commands: Commands) {
-    commands.spawn(DirectionalLightBundle {
-        directional_light: DirectionalLight {
-            illuminance: 3000.0,
-            shadows_enabled: true,
+    commands.spawn((
+        DirectionalLightBundle {
+            directional_light: DirectionalLight {
+                illuminance: SUN_ILLUMINANCE,
+                shadows_enabled: true,
+                ..default()
+            },
+            transform: Transform::from_rotation(Quat::IDENTITY).looking_at(-Vec3::Y, Vec3::Z),
             ..default()
         },
-        transform: Transform::from_rotation(Quat::IDENTITY).looking_at(-Vec3::Y, Vec3::Z),
-        ..default()
-    });
+        Sun,
+    ));
 }
 
-// TODO implement
-pub fn day_night_cycle() {}
+pub fn day_night_cycle(
+    time: Res<Time>,
+    mut world_time: ResMut<WorldTime>,
+    mut sun_query: Query<(&mut DirectionalLight, &mut Transform), With<Sun>>,
+    mut clear_color: ResMut<ClearColor>,
+    block_atlas: Res<Atlas<Block, ChunkMaterial>>,
+    mut materials: ResMut<Assets<ChunkMaterial>>,
+) {
+    world_time.advance(time.delta_seconds());
+    let sun_direction = world_time.sun_direction();
+    let daylight = world_time.daylight();
+    // sun is the most red the closer it is to the horizon
+    let sunset = 1.0 - sun_direction.y.abs().min(0.5) * 2.0;
+
+    for (mut light, mut transform) in sun_query.iter_mut() {
+        *transform = Transform::IDENTITY.looking_to(-sun_direction, Vec3::Y);
+        light.illuminance = SUN_ILLUMINANCE * daylight;
+        light.color = mix(Color::WHITE, SUNSET, sunset);
+    }
+    // only the sky light of the blocks gets dimmer, torches stay as bright as during the day.
+    // Changing the material rebuilds its bind group, so it's only touched when the value changes
+    let sky_light = NIGHT_SKY_LIGHT + (1.0 - NIGHT_SKY_LIGHT) * daylight;
+    if let Some(material) = block_atlas.material.as_ref() {
+        if materials
+            .get(material)
+            .is_some_and(|material| material.extension.sky_light != sky_light)
+        {
+            if let Some(material) = materials.get_mut(material) {
+                material.extension.sky_light = sky_light;
+            }
+        }
+    }
+    clear_color.0 = mix(
+        mix(NIGHT_SKY, DAY_SKY, daylight),
+        SUNSET,
+        sunset * daylight * 0.5,
+    );
+}
+
+// Linear interpolation between the colors, t from 0 to 1
+fn mix(a: Color, b: Color, t: f32) -> Color {
+    let a = Vec4::from(a.as_rgba_f32());
+    let b = Vec4::from(b.as_rgba_f32());
+    Color::rgba_from_array(a.lerp(b, t))
+}
+
+pub fn autosave(
+    time: Res<Time>,
+    mut timer: ResMut<AutosaveTimer>,
+    mut game_world: ResMut<GameWorld>,
+    block_registry: Res<BlockRegistry>,
+    game_mode: Res<GameMode>,
+    world_time: Res<WorldTime>,
+) {
+    if timer.0.tick(time.delta()).just_finished() {
+        if let Err(e) = game_world.save_dirty_chunks(&block_registry) {
+            error!("Autosave failed: {}", e);
+        }
+        if let Err(e) =
+            world_meta(&game_world, &game_mode, &world_time).save(&game_world.storage.root)
+        {
+            error!("Autosave failed: {}", e);
+        }
+    }
+}
+
+pub fn save_on_exit(
+    mut exit_ev: EventReader<AppExit>,
+    mut game_world: ResMut<GameWorld>,
+    block_registry: Res<BlockRegistry>,
+    game_mode: Res<GameMode>,
+    world_time: Res<WorldTime>,
+) {
+    if exit_ev.read().next().is_some() {
+        if let Err(e) = game_world.save_dirty_chunks(&block_registry) {
+            error!("Could not save the world: {}", e);
+        }
+        if let Err(e) =
+            world_meta(&game_world, &game_mode, &world_time).save(&game_world.storage.root)
+        {
+            error!("Could not save the world: {}", e);
+        }
+    }
+}
+
+fn world_meta(game_world: &GameWorld, game_mode: &GameMode, world_time: &WorldTime) -> WorldMeta {
+    WorldMeta {
+        game_mode: *game_mode,
+        time: world_time.ticks,
+        seed: Some(game_world.seed),
+    }
+}
@@ -0,0 +1,110 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use super::meta::SavedWorldMeta;
+use crate::config::{GameConfig, TimeConfig};
+
+// Time passed in the world, counted in ticks and saved with it.
+// Day starts at sunrise, the sun is the highest at a quarter of the day
+// and sets halfway through it
+#[derive(Resource, Debug, Clone)]
+pub struct WorldTime {
+    // ticks since the world was created
+    pub ticks: u64,
+    pub ticks_per_day: u64,
+    pub ticks_per_second: f32,
+    // part of the tick that passed since the last whole one
+    partial_tick: f32,
+}
+
+impl FromWorld for WorldTime {
+    // Time is saved with the world, new worlds start at the time from the config
+    fn from_world(world: &mut World) -> Self {
+        let config = world.resource::<GameConfig>();
+        let ticks = match &world.resource::<SavedWorldMeta>().0 {
+            Some(meta) => meta.time,
+            None => config.time_config.start_time,
+        };
+        WorldTime::new(ticks, &config.time_config)
+    }
+}
+
+impl WorldTime {
+    pub fn new(ticks: u64, config: &TimeConfig) -> WorldTime {
+        WorldTime {
+            ticks,
+            ticks_per_day: config.ticks_per_day.max(1),
+            ticks_per_second: config.ticks_per_second,
+            partial_tick: 0.0,
+        }
+    }
+
+    pub fn advance(&mut self, seconds: f32) {
+        let ticks = self.partial_tick + seconds * self.ticks_per_second;
+        self.ticks += ticks as u64;
+        self.partial_tick = ticks.fract();
+    }
+
+    // From 0 to 1, 0 is sunrise
+    pub fn time_of_day(&self) -> f32 {
+        ((self.ticks % self.ticks_per_day) as f32 + self.partial_tick) / self.ticks_per_day as f32
+    }
+
+    // Where the sun is on the sky, +x is east, where it rises
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = self.time_of_day() * TAU;
+        // tilted a bit south, so the sun doesn't go straight above the player
+        Vec3::new(angle.cos(), angle.sin(), -0.2).normalize()
+    }
+
+    // From 0 at night to 1 during the day, changes smoothly while the sun is near the horizon
+    pub fn daylight(&self) -> f32 {
+        let height = self.sun_direction().y;
+        let t = ((height + 0.1) / 0.3).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time_at(ticks: u64) -> WorldTime {
+        WorldTime::new(
+            ticks,
+            &TimeConfig {
+                ticks_per_day: 1000,
+                ..default()
+            },
+        )
+    }
+
+    #[test]
+    fn sun_goes_around_once_a_day() {
+        let noon = time_at(3250);
+        assert_eq!(noon.time_of_day(), 0.25);
+        assert!(noon.sun_direction().y > 0.9);
+        assert_eq!(noon.daylight(), 1.0);
+
+        let midnight = time_at(750);
+        assert!(midnight.sun_direction().y < -0.9);
+        assert_eq!(midnight.daylight(), 0.0);
+
+        // at sunrise and sunset it's neither day nor night
+        for ticks in [0, 500] {
+            let daylight = time_at(ticks).daylight();
+            assert!(daylight > 0.0 && daylight < 1.0);
+        }
+    }
+
+    #[test]
+    fn partial_ticks_add_up() {
+        let mut time = time_at(0);
+        time.ticks_per_second = 20.0;
+        for _ in 0..10 {
+            time.advance(0.025);
+        }
+        assert_eq!(time.ticks, 5);
+    }
+}